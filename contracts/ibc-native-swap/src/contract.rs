@@ -1,19 +1,76 @@
+use std::convert::TryFrom;
+
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, IbcMsg, MessageInfo, Response, StdResult,
+    coin, coins, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps,
+    DepsMut, Empty, Env, Event, HexBinary, IbcMsg, MessageInfo, Order, Reply, Response, StdError,
+    StdResult, Storage, SubMsg, Timestamp, Uint128, WasmMsg,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw20::Denom;
-use cw_utils::{must_pay, one_coin};
+use cw4::Cw4Contract;
+use cw721::{Cw721ExecuteMsg, Cw721QueryMsg, Cw721ReceiveMsg, OwnerOfResponse};
+use cw721_base::{ExecuteMsg as Cw721BaseExecuteMsg, MintMsg};
+use cw_utils::{must_pay, one_coin, NativeBalance};
+use semver::Version;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, PacketMsg, QueryMsg};
-use crate::state::{State, Swap, Token, STATE, SWAPS_A, SWAPS_B, SWAP_ID};
+use crate::events::{
+    swap_event, LOCAL_CHANNEL, SWAP_ACCEPTED, SWAP_BID, SWAP_CREATED, SWAP_REFUNDED, SWAP_SETTLED,
+};
+use crate::ibc::refund_deposit;
+use crate::ibc_helpers::{encode_packet, is_legacy};
+use crate::migrations::migrate_storage;
+use crate::migrations::v0_1::OldSwap;
+use crate::msg::{
+    DutchAuctionMsg, EnglishAuctionMsg, ExecuteMsg, HtlcMsg, Ics721OutgoingMsg, InstantiateMsg,
+    MigrateMsg, OracleQueryMsg, PacketMsg, PriceOracleMsg, PriceResponse, QueryMsg, ReceiveNftMsg,
+    SwapAmountInRoute, SwapHookExecuteMsg, SwapHookMsg, SwapOffer, SwapRouterMsg,
+};
+use crate::state::{
+    consume_flow, denom_key, escrow_in, escrow_out, record_volume, swaps_a, swaps_b,
+    swaps_for_side, update_stats, Bid, DenomRule, DutchAuction, EnglishAuction, Htlc, PriceOracle,
+    RateLimit, Rfq, RoutedAccept, State, Swap, SwapStatus, TakerAllowlist, Token,
+    AUTHORIZED_CHANNELS, DEFAULT_HOOK_GAS_LIMIT, DENOM_ALLOWLIST, DENOM_RULES, HOOKS, HOOK_FEE,
+    HOOK_GAS_LIMIT, MAX_HOOKS, NFT_BRIDGE, NFT_COLLECTIONS, PAYOUTS, POSITIONS, POSITION_NFT,
+    PRICE_ORACLE, RATE_LIMITS, RFQS_A, RFQS_B, RFQ_ID, ROUTED_ACCEPT, STATE, SWAP_ID, SWAP_ROUTER,
+    VOLUME_IN, VOLUME_OUT, VOLUME_REFUNDED,
+};
+
+/// Reply to the swap router buying the ask of `AcceptSwapWithRoute`.
+pub const ROUTE_REPLY_ID: u64 = 1;
+/// Reply to a hook that failed, which is ignored.
+pub const HOOK_REPLY_ID: u64 = 2;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:ibc-native-swap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Optional capabilities advertised through the `ContractInfo` query.
+pub const FEATURES: &[&str] = &[
+    "versioned_packets",
+    "migrate",
+    "list_swaps",
+    "indexed_swaps",
+    "htlc",
+    "dutch_auction",
+    "english_auction",
+    "rfq",
+    "recipients",
+    "local_swaps",
+    "batch",
+    "denom_rules",
+    "rate_limits",
+    "ibc_hooks",
+    "swap_router",
+    "price_oracle",
+    "swap_hooks",
+    "positions",
+    "nft_swaps",
+    "taker_allowlists",
+    "solvency",
+    "channel_authorization",
+];
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -27,6 +84,7 @@ pub fn instantiate(
         packet_lifetime: msg.packet_lifetime,
         endpoint: None,
         counterparty_endpoint: None,
+        ibc_version: None,
     };
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     STATE.save(deps.storage, &state)?;
@@ -37,6 +95,33 @@ pub fn instantiate(
         .add_attribute("owner", info.sender))
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: stored.contract,
+        });
+    }
+
+    let previous_version: Version = stored.version.parse()?;
+    let new_version: Version = CONTRACT_VERSION.parse()?;
+    if previous_version > new_version {
+        return Err(ContractError::CannotMigrateVersion {
+            previous_version: stored.version,
+            new_version: CONTRACT_VERSION.to_string(),
+        });
+    }
+
+    migrate_storage(deps.storage, &previous_version)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -49,6 +134,13 @@ pub fn execute(
             ask,
             deposit_transfer_channel_id,
             ask_transfer_channel_id,
+            htlc,
+            dutch_auction,
+            english_auction,
+            recipient,
+            refund_address,
+            allowed_takers,
+            local,
         } => execute::create(
             deps,
             env,
@@ -56,8 +148,107 @@ pub fn execute(
             ask,
             deposit_transfer_channel_id,
             ask_transfer_channel_id,
+            htlc,
+            dutch_auction,
+            english_auction,
+            recipient,
+            refund_address,
+            allowed_takers,
+            local,
+        ),
+        ExecuteMsg::AcceptSwap {
+            id,
+            recipient,
+            local: false,
+        } => execute::accept(deps, env, info, id, recipient),
+        ExecuteMsg::AcceptSwap {
+            id,
+            recipient,
+            local: true,
+        } => execute::accept_local(deps, env, info, id, recipient),
+        ExecuteMsg::BatchCreateSwaps { swaps } => execute::batch_create(deps, env, info, swaps),
+        ExecuteMsg::BatchAcceptSwaps { ids, recipient } => {
+            execute::batch_accept(deps, env, info, ids, recipient)
+        }
+        ExecuteMsg::RequestQuote { want, pay_denom } => {
+            execute::request_quote(deps, env, info, want, pay_denom)
+        }
+        ExecuteMsg::SubmitQuote {
+            rfq_id,
+            price,
+            deposit_transfer_channel_id,
+            ask_transfer_channel_id,
+        } => execute::submit_quote(
+            deps,
+            env,
+            info,
+            rfq_id,
+            price,
+            deposit_transfer_channel_id,
+            ask_transfer_channel_id,
         ),
-        ExecuteMsg::AcceptSwap { id } => execute::accept(deps, env, info, id),
+        ExecuteMsg::AcceptQuote { id } => execute::accept_quote(deps, env, info, id),
+        ExecuteMsg::CancelRequest { rfq_id } => execute::cancel_request(deps, env, info, rfq_id),
+        ExecuteMsg::PlaceBid { id } => execute::bid(deps, env, info, id),
+        ExecuteMsg::FinalizeAuction { id } => execute::finalize(deps, env, id),
+        ExecuteMsg::RevealPreimage { id, preimage } => execute::reveal(deps, env, id, preimage),
+        ExecuteMsg::ClaimSwap { id, preimage } => execute::claim(deps, env, id, preimage),
+        ExecuteMsg::RefundSwap { side, id } => execute::refund(deps, env, side, id),
+        ExecuteMsg::SetDenomRule { denom, rule } => {
+            execute::set_denom_rule(deps, info, denom, rule)
+        }
+        ExecuteMsg::SetDenomAllowlist { enabled } => {
+            execute::set_denom_allowlist(deps, info, enabled)
+        }
+        ExecuteMsg::AuthorizeChannel {
+            connection_id,
+            counterparty_port_id,
+        } => execute::authorize_channel(deps, info, connection_id, counterparty_port_id, true),
+        ExecuteMsg::RevokeChannel {
+            connection_id,
+            counterparty_port_id,
+        } => execute::authorize_channel(deps, info, connection_id, counterparty_port_id, false),
+        ExecuteMsg::SetRateLimit {
+            channel_id,
+            denom,
+            limit,
+        } => execute::set_rate_limit(deps, info, channel_id, denom, limit),
+        ExecuteMsg::ReleasePayout { id } => execute::release_payout(deps, env, id),
+        ExecuteMsg::AcceptSwapWithRoute {
+            id,
+            route,
+            max_input,
+        } => execute::accept_with_route(deps, env, info, id, route, max_input),
+        ExecuteMsg::SetSwapRouter { address } => execute::set_swap_router(deps, info, address),
+        ExecuteMsg::SetPriceOracle { oracle } => execute::set_price_oracle(deps, info, oracle),
+        ExecuteMsg::ReceiveNft(msg) => execute::receive_nft(deps, env, info, msg),
+        ExecuteMsg::SetNftBridge { address } => execute::set_nft_bridge(deps, info, address),
+        ExecuteMsg::AllowNftCollection { address } => {
+            execute::allow_nft_collection(deps, info, address, true)
+        }
+        ExecuteMsg::DisallowNftCollection { address } => {
+            execute::allow_nft_collection(deps, info, address, false)
+        }
+        ExecuteMsg::TokenizeSwap { id } => execute::tokenize(deps, info, id),
+        ExecuteMsg::CancelSwap { id } => execute::cancel(deps, env, info, id),
+        ExecuteMsg::SetPositionNft { address } => execute::set_position_nft(deps, info, address),
+        ExecuteMsg::AddHook { addr } => execute::add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => execute::remove_hook(deps, info, addr),
+        ExecuteMsg::SetHookFee { fee } => execute::set_hook_fee(deps, info, fee),
+        ExecuteMsg::SetHookGasLimit { gas_limit } => {
+            execute::set_hook_gas_limit(deps, info, gas_limit)
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        ROUTE_REPLY_ID => execute::accept_routed(deps, env),
+        HOOK_REPLY_ID => Ok(Response::new()
+            .add_attribute("method", "swap_hook_failed")
+            .add_attribute("error", msg.result.unwrap_err())),
+        id => Err(StdError::generic_err(format!("Unknown reply id {}", id)).into()),
     }
 }
 
@@ -65,6 +256,7 @@ pub mod execute {
 
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create(
         deps: DepsMut,
         env: Env,
@@ -72,107 +264,6090 @@ pub mod execute {
         ask: Token,
         deposit_transfer_channel_id: String,
         ask_transfer_channel_id: String,
+        htlc: Option<HtlcMsg>,
+        dutch_auction: Option<DutchAuctionMsg>,
+        english_auction: Option<EnglishAuctionMsg>,
+        recipient: Option<String>,
+        refund_address: Option<String>,
+        allowed_takers: Option<TakerAllowlist>,
+        local: bool,
+    ) -> Result<Response, ContractError> {
+        let deposit = one_coin(&info)?;
+        let offer = SwapOffer {
+            deposit: Token {
+                denom: Denom::Native(deposit.denom),
+                amount: deposit.amount,
+                token_id: None,
+            },
+            ask,
+            deposit_transfer_channel_id,
+            ask_transfer_channel_id,
+            htlc,
+            dutch_auction,
+            english_auction,
+            recipient,
+            refund_address,
+            allowed_takers,
+            local,
+        };
+        create_offer(deps, &env, info.sender, offer)
+    }
+
+    /// Creates several swaps, whose deposits must add up to the funds
+    /// sent exactly.
+    pub fn batch_create(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        swaps: Vec<SwapOffer>,
     ) -> Result<Response, ContractError> {
-        one_coin(&info).unwrap();
+        let mut deposits = NativeBalance::default();
+        for offer in &swaps {
+            if offer.deposit.amount.is_zero() || offer.deposit.is_nft() {
+                return Err(ContractError::InvalidBatchFunds {});
+            }
+            deposits += offer.deposit.to_coin()?;
+        }
+        let mut funds = NativeBalance(info.funds.clone());
+        funds.normalize();
+        deposits.normalize();
+        if swaps.is_empty() || deposits != funds {
+            return Err(ContractError::InvalidBatchFunds {});
+        }
+
+        let mut res = Response::new().add_attribute("method", "batch_create_swaps");
+        for offer in swaps {
+            let created = create_offer(deps.branch(), &env, info.sender.clone(), offer)?;
+            res = res
+                .add_submessages(created.messages)
+                .add_events(created.events);
+        }
+        Ok(res)
+    }
+
+    fn create_offer(
+        deps: DepsMut,
+        env: &Env,
+        maker: Addr,
+        offer: SwapOffer,
+    ) -> Result<Response, ContractError> {
+        let SwapOffer {
+            deposit,
+            ask,
+            deposit_transfer_channel_id,
+            ask_transfer_channel_id,
+            htlc,
+            dutch_auction,
+            english_auction,
+            recipient,
+            refund_address,
+            allowed_takers,
+            local,
+        } = offer;
+        let refund_address = refund_address
+            .map(|addr| deps.api.addr_validate(&addr))
+            .transpose()?;
+        if english_auction.is_some() && (htlc.is_some() || dutch_auction.is_some()) {
+            return Err(ContractError::IncompatibleModes {});
+        }
+        // Hash locks and bidding only make sense across chains.
+        if local && (htlc.is_some() || english_auction.is_some()) {
+            return Err(ContractError::IncompatibleModes {});
+        }
+        // An NFT has no price to decline or bid up.
+        let nft = deposit.is_nft() || ask.is_nft();
+        if (nft && local)
+            || (ask.is_nft() && (dutch_auction.is_some() || english_auction.is_some()))
+        {
+            return Err(ContractError::IncompatibleModes {});
+        }
+        if ask.is_nft() && (ask.amount != Uint128::one() || matches!(ask.denom, Denom::Native(_))) {
+            return Err(ContractError::InvalidNft {});
+        }
+        if deposit.is_nft() && NFT_BRIDGE.may_load(deps.storage)?.is_none() {
+            return Err(ContractError::NoNftBridge {});
+        }
+        if let Some(TakerAllowlist::Addresses(takers)) = &allowed_takers {
+            if takers.is_empty() {
+                return Err(ContractError::EmptyAllowlist {});
+            }
+        }
 
         let state = STATE.load(deps.storage)?;
+        let htlc = htlc.map(|htlc| lock(env, &state, htlc)).transpose()?;
+        let dutch_auction = dutch_auction
+            .map(|auction| schedule(env, &ask, auction))
+            .transpose()?;
+        let english_auction = english_auction
+            .map(|auction| open_auction(env, auction))
+            .transpose()?;
+        let recipient = recipient
+            .map(|recipient| {
+                // Makers that can not hold funds name a recipient on
+                // whichever chain the ask is sent to.
+                let payee = refund_address.is_none().then_some(&maker);
+                validate_recipient(recipient, payee)
+            })
+            .transpose()?;
+        if refund_address.is_some() && recipient.is_none() {
+            return Err(ContractError::RecipientRequired {});
+        }
 
-        let swap_id = SWAP_ID.load(deps.storage)?;
         let swap = Swap {
-            deposit: Token {
-                denom: Denom::Native(info.funds[0].denom.clone()),
-                amount: info.funds[0].amount,
-            },
-            deposit_address: info.sender.clone(),
+            deposit,
+            deposit_address: maker,
             deposit_transfer_channel_id,
-            ask: ask,
+            ask,
             ask_address: None,
             ask_transfer_channel_id,
+            status: if local {
+                SwapStatus::Open
+            } else {
+                SwapStatus::Pending
+            },
+            htlc,
+            taker: None,
+            dutch_auction,
+            english_auction,
+            rfq_id: None,
+            recipient,
+            taker_recipient: None,
+            refund_address,
+            allowed_takers,
+            local,
+        };
+
+        list_swap(deps, env.clone(), state, swap, "create_swap")
+    }
+
+    /// Checks that `recipient` is a bech32 address, on the same chain as
+    /// `payee` if given, whose payout it receives instead.
+    fn validate_recipient(recipient: String, payee: Option<&Addr>) -> Result<Addr, ContractError> {
+        let invalid = || ContractError::InvalidRecipient {
+            recipient: recipient.clone(),
+        };
+        let (prefix, _, _) = bech32::decode(&recipient).map_err(|_| invalid())?;
+        if let Some(Ok((payee_prefix, _, _))) = payee.map(|payee| bech32::decode(payee.as_str())) {
+            if prefix != payee_prefix {
+                return Err(invalid());
+            }
+        }
+        Ok(Addr::unchecked(recipient))
+    }
+
+    /// Escrows a new swap on side A and sends its listing to side B.
+    /// Local swaps are open to takers on this chain right away.
+    fn list_swap(
+        deps: DepsMut,
+        env: Env,
+        state: State,
+        swap: Swap,
+        method: &str,
+    ) -> Result<Response, ContractError> {
+        // Legacy peers would list the swap without the terms they do
+        // not know, so only plain swaps are sent to them.
+        if !swap.local && is_legacy(state.ibc_version.as_deref()) {
+            OldSwap::try_from(&swap)?;
+        }
+        check_denom(deps.storage, &swap.deposit)?;
+        check_denom(deps.storage, &swap.ask)?;
+        let deviation = check_price(deps.as_ref(), &swap.deposit, &swap.ask)?;
+        let swap_id = SWAP_ID.load(deps.storage)?;
+        swaps_a().save(deps.storage, swap_id, &swap)?;
+        SWAP_ID.save(deps.storage, &(swap_id.checked_add(1).unwrap()))?;
+        update_stats(deps.storage, |stats| stats.swaps_created += 1)?;
+        record_volume(deps.storage, VOLUME_IN, &swap.deposit)?;
+        escrow_in(deps.storage, &swap.deposit)?;
+
+        let channel_id = if swap.local {
+            LOCAL_CHANNEL.to_string()
+        } else {
+            state
+                .endpoint
+                .ok_or(ContractError::NotConnected {})?
+                .channel_id
+        };
+        let mut event = swap_event(SWAP_CREATED, swap_id, "A", &channel_id, &swap, None);
+        if let Some(oracle_price) = deviation {
+            event = event.add_attribute("oracle_price", oracle_price.to_string());
+        }
+        let hook = SwapHookMsg::SwapCreated {
+            id: swap_id,
+            side: "A".to_string(),
+            swap: swap.clone(),
         };
+        let hooks = swap_hooks(deps.storage, hook)?;
+        let res = Response::new()
+            .add_attribute("method", method)
+            .add_event(event);
+        if swap.local {
+            return Ok(res.add_submessages(hooks));
+        }
 
         let packet = PacketMsg::CreateSideB {
             id: swap_id,
             swap: swap.clone(),
         };
         let msg = IbcMsg::SendPacket {
-            channel_id: state.endpoint.unwrap().channel_id,
-            data: to_binary(&packet)?,
+            channel_id,
+            data: encode_packet(state.ibc_version.as_deref(), &packet)?,
             timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
         };
 
-        SWAPS_A.save(deps.storage, swap_id, &swap)?;
-        SWAP_ID.save(deps.storage, &(swap_id.checked_add(1).unwrap()))?;
+        Ok(res.add_message(msg).add_submessages(hooks))
+    }
+
+    /// Settles a local swap in one transaction: the taker's payment is
+    /// sent to the maker and the deposit to the taker.
+    pub fn accept_local(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut swap = swaps_a().load(deps.storage, id)?;
+        if !swap.local {
+            return Err(ContractError::NotLocal {});
+        }
+        if swap.status != SwapStatus::Open {
+            return Err(ContractError::SwapNotOpen {});
+        }
+        let recipient = recipient
+            .map(|recipient| validate_recipient(recipient, Some(&info.sender)))
+            .transpose()?;
+        check_taker(deps.as_ref(), &swap, &info.sender)?;
+
+        let ask = swap.ask_at(env.block.time);
+        check_denom(deps.storage, &swap.deposit)?;
+        check_denom(deps.storage, &ask)?;
+        let deviation = check_auction_price(deps.as_ref(), &swap, &ask)?;
+        let paid = match &ask.denom {
+            Denom::Native(denom) => must_pay(&info, denom)?,
+            Denom::Cw20(_) => return Err(ContractError::Cw20Unsupported {}),
+        };
+        if ask.amount > paid {
+            return Err(ContractError::InsufficientFunds {});
+        }
+
+        swap.ask = ask;
+        swap.taker = Some(info.sender.clone());
+        swap.taker_recipient = recipient;
+        if let Some(holder) = position_holder(deps.as_ref(), id)? {
+            swap.recipient = Some(holder);
+        }
+        swaps_a().remove(deps.storage, id)?;
+        POSITIONS.remove(deps.storage, id);
+        update_stats(deps.storage, |stats| stats.swaps_settled += 1)?;
+        record_volume(deps.storage, VOLUME_IN, &swap.ask)?;
+        record_volume(deps.storage, VOLUME_OUT, &swap.ask)?;
+        record_volume(deps.storage, VOLUME_OUT, &swap.deposit)?;
+        escrow_out(deps.storage, &swap.deposit)?;
+
+        let mut res = Response::new()
+            .add_message(BankMsg::Send {
+                to_address: swap.maker_payee().to_string(),
+                amount: vec![swap.ask.to_coin()?],
+            })
+            .add_message(BankMsg::Send {
+                to_address: swap.taker_payee().unwrap().to_string(),
+                amount: vec![swap.deposit.to_coin()?],
+            });
+        if paid > swap.ask.amount {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![Token {
+                    denom: swap.ask.denom.clone(),
+                    amount: paid - swap.ask.amount,
+                    token_id: None,
+                }
+                .to_coin()?],
+            });
+        }
+
+        let mut event = swap_event(SWAP_SETTLED, id, "A", LOCAL_CHANNEL, &swap, None);
+        if let Some(oracle_price) = deviation {
+            event = event.add_attribute("oracle_price", oracle_price.to_string());
+        }
+        let hook = SwapHookMsg::SwapSettled {
+            id,
+            side: "A".to_string(),
+            swap,
+        };
+        Ok(res
+            .add_attribute("method", "accept_local")
+            .add_event(event)
+            .add_submessages(swap_hooks(deps.storage, hook)?))
+    }
+
+    /// Accepts several side B swaps at their current ask, paid out of
+    /// the funds sent. Whatever is left over is returned.
+    pub fn batch_accept(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        ids: Vec<u64>,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut asks = vec![];
+        for id in &ids {
+            let swap = swaps_b().load(deps.storage, *id)?;
+            if swap.ask.is_nft() {
+                return Err(ContractError::InvalidNft {});
+            }
+            asks.push(swap.ask_at(env.block.time).to_coin()?);
+        }
+        let change = (NativeBalance(info.funds.clone()) - asks.clone())
+            .map_err(|_| ContractError::InsufficientFunds {})?;
+
+        let mut res = Response::new().add_attribute("method", "batch_accept_swaps");
+        for (id, ask) in ids.into_iter().zip(asks) {
+            let info = MessageInfo {
+                sender: info.sender.clone(),
+                funds: vec![ask],
+            };
+            let accepted = accept(deps.branch(), env.clone(), info, id, recipient.clone())?;
+            res = res
+                .add_submessages(accepted.messages)
+                .add_events(accepted.events);
+        }
+        if !change.is_empty() {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: change.into_vec(),
+            });
+        }
+        Ok(res)
+    }
+
+    pub fn set_denom_rule(
+        deps: DepsMut,
+        info: MessageInfo,
+        denom: String,
+        rule: Option<DenomRule>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        match rule {
+            Some(rule) => DENOM_RULES.save(deps.storage, &denom, &rule)?,
+            None => DENOM_RULES.remove(deps.storage, &denom),
+        }
 
         Ok(Response::new()
-            .add_message(msg)
-            .add_attribute("method", "create_swap"))
+            .add_attribute("method", "set_denom_rule")
+            .add_attribute("denom", denom))
     }
 
-    pub fn accept(
+    pub fn set_denom_allowlist(
+        deps: DepsMut,
+        info: MessageInfo,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        DENOM_ALLOWLIST.save(deps.storage, &enabled)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "set_denom_allowlist")
+            .add_attribute("enabled", enabled.to_string()))
+    }
+
+    pub fn authorize_channel(
+        deps: DepsMut,
+        info: MessageInfo,
+        connection_id: String,
+        counterparty_port_id: String,
+        authorized: bool,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let key = (connection_id.as_str(), counterparty_port_id.as_str());
+        if authorized {
+            AUTHORIZED_CHANNELS.save(deps.storage, key, &Empty {})?;
+        } else {
+            AUTHORIZED_CHANNELS.remove(deps.storage, key);
+        }
+
+        Ok(Response::new()
+            .add_attribute(
+                "method",
+                if authorized {
+                    "authorize_channel"
+                } else {
+                    "revoke_channel"
+                },
+            )
+            .add_attribute("connection_id", connection_id)
+            .add_attribute("counterparty_port_id", counterparty_port_id))
+    }
+
+    pub fn set_rate_limit(
         deps: DepsMut,
-        env: Env,
         info: MessageInfo,
+        channel_id: String,
+        denom: String,
+        limit: Option<RateLimit>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        match limit {
+            Some(limit) if limit.window == 0 => return Err(ContractError::InvalidRateLimit {}),
+            Some(limit) => RATE_LIMITS.save(deps.storage, (&channel_id, &denom), &limit)?,
+            None => RATE_LIMITS.remove(deps.storage, (&channel_id, &denom)),
+        }
+
+        Ok(Response::new()
+            .add_attribute("method", "set_rate_limit")
+            .add_attribute("channel_id", channel_id)
+            .add_attribute("denom", denom))
+    }
+
+    pub fn release_payout(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+        let payout = PAYOUTS.load(deps.storage, id)?;
+        let swap = payout.swap;
+        if !consume_flow(
+            deps.storage,
+            env.block.time,
+            VOLUME_OUT,
+            &swap.ask_transfer_channel_id,
+            &swap.ask,
+        )? {
+            return Err(ContractError::RateLimited {
+                channel_id: swap.ask_transfer_channel_id,
+                denom: denom_key(&swap.ask.denom),
+            });
+        }
+        PAYOUTS.remove(deps.storage, id);
+
+        let (transfer_msg, event, hooks) = pay_maker(deps, &env, id, &swap, &payout.channel_id)?;
+        Ok(Response::new()
+            .add_message(transfer_msg)
+            .add_attribute("method", "release_payout")
+            .add_event(event)
+            .add_submessages(hooks))
+    }
+
+    /// Pays the ask of a swap settled on side B to its maker.
+    pub fn pay_maker(
+        deps: DepsMut,
+        env: &Env,
         id: u64,
+        swap: &Swap,
+        channel_id: &str,
+    ) -> Result<(CosmosMsg, Event, Vec<SubMsg>), ContractError> {
+        let state = STATE.load(deps.storage)?;
+        update_stats(deps.storage, |stats| stats.swaps_settled += 1)?;
+        record_volume(deps.storage, VOLUME_OUT, &swap.ask)?;
+        escrow_out(deps.storage, &swap.ask)?;
+        let event = swap_event(SWAP_SETTLED, id, "B", channel_id, swap, None);
+        let hook = SwapHookMsg::SwapSettled {
+            id,
+            side: "B".to_string(),
+            swap: swap.clone(),
+        };
+        let hooks = swap_hooks(deps.storage, hook)?;
+        let transfer_msg = transfer_token(
+            deps.storage,
+            &swap.ask,
+            &swap.ask_transfer_channel_id,
+            swap.maker_payee().as_str(),
+            env.block.time.plus_seconds(state.packet_lifetime),
+        )?;
+        Ok((transfer_msg, event, hooks))
+    }
+
+    /// Checks a deposit or ask against the owner's denom rules.
+    pub fn check_denom(storage: &dyn Storage, token: &Token) -> Result<(), ContractError> {
+        let denom = denom_key(&token.denom);
+        let rule = match DENOM_RULES.may_load(storage, &denom)? {
+            Some(rule) if !rule.denied => rule,
+            None if !DENOM_ALLOWLIST.may_load(storage)?.unwrap_or_default() => return Ok(()),
+            _ => return Err(ContractError::DenomNotAllowed { denom }),
+        };
+        // An NFT is allowed or denied by its collection, it has no size.
+        if token.is_nft() {
+            return Ok(());
+        }
+        let too_small = rule.min_amount.is_some_and(|min| token.amount < min);
+        let too_large = rule.max_amount.is_some_and(|max| token.amount > max);
+        if too_small || too_large {
+            return Err(ContractError::InvalidSwapSize { denom });
+        }
+        Ok(())
+    }
+
+    pub fn set_nft_bridge(
+        deps: DepsMut,
+        info: MessageInfo,
+        address: String,
     ) -> Result<Response, ContractError> {
         let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let bridge = deps.api.addr_validate(&address)?;
+        NFT_BRIDGE.save(deps.storage, &bridge)?;
 
-        let swap = SWAPS_B.load(deps.storage, id)?;
+        Ok(Response::new()
+            .add_attribute("method", "set_nft_bridge")
+            .add_attribute("bridge", bridge))
+    }
 
-        if swap.ask_address.is_some() {
-            if info.sender.to_string() != swap.ask_address.unwrap() {
-                return Err(ContractError::Unauthorized {});
+    pub fn allow_nft_collection(
+        deps: DepsMut,
+        info: MessageInfo,
+        address: String,
+        allowed: bool,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let collection = deps.api.addr_validate(&address)?;
+        if allowed {
+            NFT_COLLECTIONS.save(deps.storage, &collection, &Empty {})?;
+        } else {
+            NFT_COLLECTIONS.remove(deps.storage, &collection);
+        }
+
+        Ok(Response::new()
+            .add_attribute(
+                "method",
+                if allowed {
+                    "allow_nft_collection"
+                } else {
+                    "disallow_nft_collection"
+                },
+            )
+            .add_attribute("collection", collection))
+    }
+
+    /// Creates a swap of the NFT sent, or accepts a swap asking for it.
+    /// Anyone can send a `ReceiveNft`, so only contracts on the owner's
+    /// collection allowlist are trusted to have actually sent the NFT.
+    pub fn receive_nft(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        wrapper: Cw721ReceiveMsg,
+    ) -> Result<Response, ContractError> {
+        if deps.querier.query_wasm_contract_info(&info.sender).is_err() {
+            return Err(ContractError::Unauthorized {});
+        }
+        if !NFT_COLLECTIONS.has(deps.storage, &info.sender) {
+            return Err(ContractError::NftCollectionNotAllowed {
+                collection: info.sender.into_string(),
+            });
+        }
+        let sender = deps.api.addr_validate(&wrapper.sender)?;
+        let nft = Token::nft(info.sender.as_str(), wrapper.token_id);
+        match from_binary(&wrapper.msg)? {
+            ReceiveNftMsg::CreateSwap {
+                ask,
+                deposit_transfer_channel_id,
+                ask_transfer_channel_id,
+                recipient,
+                allowed_takers,
+            } => {
+                let offer = SwapOffer {
+                    deposit: nft,
+                    ask,
+                    deposit_transfer_channel_id,
+                    ask_transfer_channel_id,
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    recipient,
+                    refund_address: None,
+                    allowed_takers,
+                    local: false,
+                };
+                create_offer(deps, &env, sender, offer)
+            }
+            ReceiveNftMsg::AcceptSwap { id, recipient } => {
+                let info = MessageInfo {
+                    sender,
+                    funds: vec![],
+                };
+                accept_with(deps, env, info, Some(nft), id, recipient)
             }
         }
+    }
 
-        match swap.ask.denom {
-            Denom::Native(denom) => {
-                must_pay(&info, &denom).unwrap();
+    /// Sends a token this contract holds to `recipient` on this chain.
+    pub fn send_token(token: &Token, recipient: &str) -> StdResult<CosmosMsg> {
+        let (collection, token_id) = match (&token.denom, &token.token_id) {
+            (Denom::Cw20(collection), Some(token_id)) => (collection, token_id.clone()),
+            _ => {
+                return Ok(BankMsg::Send {
+                    to_address: recipient.to_string(),
+                    amount: vec![token.to_coin()?],
+                }
+                .into())
             }
-            Denom::Cw20(_) => unimplemented!(),
         };
+        let transfer = Cw721ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id,
+        };
+        Ok(WasmMsg::Execute {
+            contract_addr: collection.to_string(),
+            msg: to_binary(&transfer)?,
+            funds: vec![],
+        }
+        .into())
+    }
 
-        if swap.ask.amount > info.clone().funds[0].amount {
-            return Err(ContractError::InsufficientFunds {});
+    /// Sends a token this contract holds to `receiver` at the other end
+    /// of `channel_id`, coins over ICS-20 and NFTs through the ICS-721
+    /// bridge.
+    pub fn transfer_token(
+        storage: &dyn Storage,
+        token: &Token,
+        channel_id: &str,
+        receiver: &str,
+        timeout: Timestamp,
+    ) -> Result<CosmosMsg, ContractError> {
+        let (collection, token_id) = match (&token.denom, &token.token_id) {
+            (Denom::Cw20(collection), Some(token_id)) => (collection, token_id.clone()),
+            _ => {
+                return Ok(IbcMsg::Transfer {
+                    channel_id: channel_id.to_string(),
+                    to_address: receiver.to_string(),
+                    amount: token.to_coin()?,
+                    timeout: timeout.into(),
+                }
+                .into())
+            }
+        };
+        let bridge = NFT_BRIDGE
+            .may_load(storage)?
+            .ok_or(ContractError::NoNftBridge {})?;
+        let outgoing = Ics721OutgoingMsg {
+            receiver: receiver.to_string(),
+            channel_id: channel_id.to_string(),
+            timeout: timeout.into(),
+        };
+        let send = Cw721ExecuteMsg::SendNft {
+            contract: bridge.into_string(),
+            token_id,
+            msg: to_binary(&outgoing)?,
+        };
+        Ok(WasmMsg::Execute {
+            contract_addr: collection.to_string(),
+            msg: to_binary(&send)?,
+            funds: vec![],
         }
+        .into())
+    }
 
-        let channel_id = state.endpoint.unwrap().channel_id;
+    pub fn set_position_nft(
+        deps: DepsMut,
+        info: MessageInfo,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let nft = deps.api.addr_validate(&address)?;
+        POSITION_NFT.save(deps.storage, &nft)?;
 
-        let accept_msg = PacketMsg::AcceptSideA {
-            id,
-            sender: info.sender.to_string(),
-        };
+        Ok(Response::new()
+            .add_attribute("method", "set_position_nft")
+            .add_attribute("nft", nft))
+    }
 
-        let packet_msg = IbcMsg::SendPacket {
-            channel_id: channel_id,
-            data: to_binary(&accept_msg)?,
-            timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
+    /// Mints the maker of a side A swap an NFT of its position, with
+    /// the swap id as token id.
+    pub fn tokenize(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+        let nft = POSITION_NFT
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoPositionNft {})?;
+        let swap = swaps_a().load(deps.storage, id)?;
+        if info.sender != swap.deposit_address {
+            return Err(ContractError::Unauthorized {});
+        }
+        if swap.status == SwapStatus::Accepted {
+            return Err(ContractError::SwapNotOpen {});
+        }
+        // HTLC makers are paid on side B when they reveal the preimage.
+        if swap.htlc.is_some() {
+            return Err(ContractError::IncompatibleModes {});
+        }
+        if POSITIONS.has(deps.storage, id) {
+            return Err(ContractError::AlreadyTokenized {});
+        }
+        POSITIONS.save(deps.storage, id, &nft)?;
+
+        let mint = Cw721BaseExecuteMsg::<Empty, Empty>::Mint(MintMsg {
+            token_id: id.to_string(),
+            owner: info.sender.to_string(),
+            token_uri: None,
+            extension: Empty {},
+        });
+        let mint_msg = WasmMsg::Execute {
+            contract_addr: nft.to_string(),
+            msg: to_binary(&mint)?,
+            funds: vec![],
         };
 
         Ok(Response::new()
-            .add_message(packet_msg)
-            .add_attribute("method", "accept_swap"))
+            .add_message(mint_msg)
+            .add_attribute("method", "tokenize_swap")
+            .add_attribute("id", id.to_string()))
     }
-}
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetSwap { side, id } => to_binary(&query::get_swap(deps, side, id)?),
+    /// Holder of a tokenized swap's position, or `None` for swaps that
+    /// were never tokenized. Failing to query the owner of a position,
+    /// even a burned one, is an error, as the maker may have sold it.
+    pub fn position_holder(deps: Deps, id: u64) -> StdResult<Option<Addr>> {
+        let nft = match POSITIONS.may_load(deps.storage, id)? {
+            Some(nft) => nft,
+            None => return Ok(None),
+        };
+        let query = Cw721QueryMsg::OwnerOf {
+            token_id: id.to_string(),
+            include_expired: None,
+        };
+        let res: OwnerOfResponse = deps.querier.query_wasm_smart(nft, &query)?;
+        Ok(Some(Addr::unchecked(res.owner)))
     }
-}
 
-pub mod query {
-    use cosmwasm_std::StdError;
+    /// Cancels an open side A swap. Local swaps are refunded right
+    /// away, others once side B withdrew the listing. Untokenized swaps
+    /// may also be cancelled by their refund address, which is paid the
+    /// deposit back anyway and may be the only key behind a maker
+    /// derived by ibc-hooks.
+    pub fn cancel(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        let swap = swaps_a().load(deps.storage, id)?;
+        let authorized = match position_holder(deps.as_ref(), id)? {
+            Some(holder) => info.sender == holder,
+            None => {
+                info.sender == swap.deposit_address
+                    || swap.refund_address.as_ref() == Some(&info.sender)
+            }
+        };
+        if !authorized {
+            return Err(ContractError::Unauthorized {});
+        }
+        if swap.status != SwapStatus::Open {
+            return Err(ContractError::SwapNotOpen {});
+        }
 
-    use super::*;
+        let res = Response::new().add_attribute("method", "cancel_swap");
+        if swap.local {
+            let (refund_msg, event, hooks) = refund_deposit(deps, id, &swap, LOCAL_CHANNEL, true)?;
+            return Ok(res
+                .add_message(refund_msg)
+                .add_event(event)
+                .add_submessages(hooks));
+        }
 
-    pub fn get_swap(deps: Deps, side: String, id: u64) -> StdResult<Swap> {
-        if side == "A".to_string() {
-            return Ok(SWAPS_A.load(deps.storage, id)?);
-        } else if side == "B".to_string() {
-            return Ok(SWAPS_B.load(deps.storage, id)?);
+        let packet_msg = IbcMsg::SendPacket {
+            channel_id: state
+                .endpoint
+                .ok_or(ContractError::NotConnected {})?
+                .channel_id,
+            data: encode_packet(state.ibc_version.as_deref(), &PacketMsg::CancelSideB { id })?,
+            timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
+        };
+        Ok(res.add_message(packet_msg))
+    }
+
+    pub fn add_hook(
+        deps: DepsMut,
+        info: MessageInfo,
+        addr: String,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        let hook = deps.api.addr_validate(&addr)?;
+        let mut res = Response::new().add_attribute("method", "add_hook");
+        if info.sender != state.owner {
+            // Anyone else may only register a contract of their own,
+            // paying the fee to the owner.
+            let fee = HOOK_FEE
+                .may_load(deps.storage)?
+                .ok_or(ContractError::Unauthorized {})?;
+            if hook != info.sender || deps.querier.query_wasm_contract_info(&hook).is_err() {
+                return Err(ContractError::Unauthorized {});
+            }
+            let paid = must_pay(&info, &fee.denom).unwrap_or_default();
+            if paid < fee.amount {
+                return Err(ContractError::InsufficientFunds {});
+            }
+            res = res.add_message(BankMsg::Send {
+                to_address: state.owner.to_string(),
+                amount: coins(paid.u128(), fee.denom),
+            });
+        }
+        if HOOKS.query_hooks(deps.as_ref())?.hooks.len() >= MAX_HOOKS {
+            return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+        }
+        HOOKS.add_hook(deps.storage, hook.clone())?;
+
+        Ok(res.add_attribute("hook", hook))
+    }
+
+    pub fn remove_hook(
+        deps: DepsMut,
+        info: MessageInfo,
+        addr: String,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        let hook = deps.api.addr_validate(&addr)?;
+        if info.sender != state.owner && info.sender != hook {
+            return Err(ContractError::Unauthorized {});
+        }
+        HOOKS.remove_hook(deps.storage, hook.clone())?;
+
+        Ok(Response::new()
+            .add_attribute("method", "remove_hook")
+            .add_attribute("hook", hook))
+    }
+
+    pub fn set_hook_fee(
+        deps: DepsMut,
+        info: MessageInfo,
+        fee: Option<Coin>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        match fee {
+            Some(fee) => HOOK_FEE.save(deps.storage, &fee)?,
+            None => HOOK_FEE.remove(deps.storage),
+        }
+
+        Ok(Response::new().add_attribute("method", "set_hook_fee"))
+    }
+
+    pub fn set_hook_gas_limit(
+        deps: DepsMut,
+        info: MessageInfo,
+        gas_limit: u64,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        HOOK_GAS_LIMIT.save(deps.storage, &gas_limit)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "set_hook_gas_limit")
+            .add_attribute("gas_limit", gas_limit.to_string()))
+    }
+
+    /// Notifies the hooks of a swap transition. Each hook runs in a
+    /// submessage whose failure is ignored, so a broken hook can not
+    /// hold up a swap. Running out of gas only counts as a failure
+    /// under a gas limit, without one it aborts the whole transaction.
+    pub fn swap_hooks(storage: &dyn Storage, msg: SwapHookMsg) -> StdResult<Vec<SubMsg>> {
+        let msg = to_binary(&SwapHookExecuteMsg::SwapHook(msg))?;
+        let gas_limit = HOOK_GAS_LIMIT
+            .may_load(storage)?
+            .unwrap_or(DEFAULT_HOOK_GAS_LIMIT);
+        HOOKS.prepare_hooks(storage, |hook| {
+            let execute = WasmMsg::Execute {
+                contract_addr: hook.into_string(),
+                msg: msg.clone(),
+                funds: vec![],
+            };
+            Ok(SubMsg::reply_on_error(execute, HOOK_REPLY_ID).with_gas_limit(gas_limit))
+        })
+    }
+
+    pub fn set_price_oracle(
+        deps: DepsMut,
+        info: MessageInfo,
+        oracle: Option<PriceOracleMsg>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let res = Response::new().add_attribute("method", "set_price_oracle");
+        match oracle {
+            Some(oracle) => {
+                let address = deps.api.addr_validate(&oracle.address)?;
+                let oracle = PriceOracle {
+                    address,
+                    tolerance: oracle.tolerance,
+                    enforce: oracle.enforce,
+                };
+                PRICE_ORACLE.save(deps.storage, &oracle)?;
+                Ok(res.add_attribute("oracle", oracle.address))
+            }
+            None => {
+                PRICE_ORACLE.remove(deps.storage);
+                Ok(res)
+            }
+        }
+    }
+
+    /// Compares the price of `ask` per unit of `deposit` to the
+    /// oracle's. Swaps deviating by more than the tolerance are
+    /// rejected, or the oracle's price is returned to flag them.
+    /// Swaps the oracle can not price are rejected, so that an outage
+    /// does not let them through unchecked.
+    pub fn check_price(
+        deps: Deps,
+        deposit: &Token,
+        ask: &Token,
+    ) -> Result<Option<Decimal>, ContractError> {
+        // NFTs have no fungible price to compare against.
+        if deposit.is_nft() || ask.is_nft() {
+            return Ok(None);
+        }
+        let oracle = match PRICE_ORACLE.may_load(deps.storage)? {
+            Some(oracle) => oracle,
+            None => return Ok(None),
+        };
+        let base_denom = denom_key(&deposit.denom);
+        let quote_denom = denom_key(&ask.denom);
+        let query = OracleQueryMsg::Price {
+            base_denom: base_denom.clone(),
+            quote_denom: quote_denom.clone(),
+        };
+        let oracle_price = match deps
+            .querier
+            .query_wasm_smart::<PriceResponse>(&oracle.address, &query)
+        {
+            Ok(res) if !res.price.is_zero() => res.price,
+            _ => {
+                return Err(ContractError::NoOraclePrice {
+                    base_denom,
+                    quote_denom,
+                })
+            }
+        };
+        // A price too large to represent is as far off as it gets.
+        let deviates = match Decimal::checked_from_ratio(ask.amount, deposit.amount) {
+            Ok(price) => {
+                let diff = if price > oracle_price {
+                    price - oracle_price
+                } else {
+                    oracle_price - price
+                };
+                diff > oracle_price
+                    .checked_mul(oracle.tolerance)
+                    .map_err(StdError::from)?
+            }
+            Err(_) => true,
+        };
+        match (deviates, oracle.enforce) {
+            (false, _) => Ok(None),
+            (true, true) => Err(ContractError::PriceDeviation { oracle_price }),
+            (true, false) => Ok(Some(oracle_price)),
+        }
+    }
+
+    pub fn set_swap_router(
+        deps: DepsMut,
+        info: MessageInfo,
+        address: String,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let router = deps.api.addr_validate(&address)?;
+        SWAP_ROUTER.save(deps.storage, &router)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "set_swap_router")
+            .add_attribute("router", router))
+    }
+
+    /// Has the swap router buy the ask of a side B swap with the funds
+    /// sent, and accepts the swap in the reply.
+    pub fn accept_with_route(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+        route: Vec<SwapAmountInRoute>,
+        max_input: Uint128,
+    ) -> Result<Response, ContractError> {
+        let router = SWAP_ROUTER
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoSwapRouter {})?;
+        let swap = swaps_b().load(deps.storage, id)?;
+        if swap.status != SwapStatus::Open {
+            return Err(ContractError::SwapNotOpen {});
+        }
+        let ask = swap.ask_at(env.block.time);
+        if ask.is_nft() {
+            return Err(ContractError::InvalidRoute {});
+        }
+        let ask_denom = match &ask.denom {
+            Denom::Native(denom) => denom.clone(),
+            Denom::Cw20(_) => return Err(ContractError::Cw20Unsupported {}),
+        };
+        let input = one_coin(&info)?;
+        let routed_denom = route.last().map(|hop| hop.token_out_denom.as_str());
+        if input.denom == ask_denom
+            || routed_denom != Some(ask_denom.as_str())
+            || max_input.is_zero()
+            || max_input > input.amount
+        {
+            return Err(ContractError::InvalidRoute {});
+        }
+
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, &ask_denom)?
+            .amount;
+        ROUTED_ACCEPT.save(
+            deps.storage,
+            &RoutedAccept {
+                id,
+                taker: info.sender.clone(),
+                balance,
+            },
+        )?;
+
+        let swap_msg = WasmMsg::Execute {
+            contract_addr: router.to_string(),
+            msg: to_binary(&SwapRouterMsg::Swap {
+                input_coin: coin(max_input.u128(), &input.denom),
+                output_denom: ask_denom,
+                minimum_output_amount: ask.amount,
+                route,
+            })?,
+            funds: vec![coin(max_input.u128(), &input.denom)],
+        };
+        let mut res = Response::new()
+            .add_submessage(SubMsg::reply_on_success(swap_msg, ROUTE_REPLY_ID))
+            .add_attribute("method", "accept_swap_with_route");
+        if input.amount > max_input {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![coin((input.amount - max_input).u128(), input.denom)],
+            });
+        }
+        Ok(res)
+    }
+
+    /// Accepts a routed swap with the ask the router bought, returning
+    /// whatever it bought on top.
+    pub fn accept_routed(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+        let pending = ROUTED_ACCEPT.load(deps.storage)?;
+        ROUTED_ACCEPT.remove(deps.storage);
+
+        let swap = swaps_b().load(deps.storage, pending.id)?;
+        let ask = swap.ask_at(env.block.time);
+        let denom = denom_key(&ask.denom);
+        let bought = deps
+            .querier
+            .query_balance(&env.contract.address, &denom)?
+            .amount
+            .checked_sub(pending.balance)
+            .map_err(StdError::from)?;
+        if bought < ask.amount {
+            return Err(ContractError::InsufficientFunds {});
+        }
+
+        let info = MessageInfo {
+            sender: pending.taker.clone(),
+            funds: vec![ask.to_coin()?],
+        };
+        let mut res = accept(deps, env, info, pending.id, None)?;
+        if bought > ask.amount {
+            res = res.add_message(BankMsg::Send {
+                to_address: pending.taker.to_string(),
+                amount: vec![coin((bought - ask.amount).u128(), denom)],
+            });
+        }
+        Ok(res)
+    }
+
+    pub fn request_quote(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        want: Token,
+        pay_denom: Denom,
+    ) -> Result<Response, ContractError> {
+        // Quotes are paid for in coins, an NFT has no quantity to price.
+        if want.is_nft() {
+            return Err(ContractError::InvalidNft {});
+        }
+        let state = STATE.load(deps.storage)?;
+
+        let rfq_id = RFQ_ID.may_load(deps.storage)?.unwrap_or_default();
+        let rfq = Rfq {
+            requester: info.sender,
+            want,
+            pay_denom,
+        };
+        RFQS_B.save(deps.storage, rfq_id, &rfq)?;
+        RFQ_ID.save(deps.storage, &(rfq_id + 1))?;
+
+        let packet_msg = IbcMsg::SendPacket {
+            channel_id: state
+                .endpoint
+                .ok_or(ContractError::NotConnected {})?
+                .channel_id,
+            data: encode_packet(
+                state.ibc_version.as_deref(),
+                &PacketMsg::RequestQuote { id: rfq_id, rfq },
+            )?,
+            timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
+        };
+
+        Ok(Response::new()
+            .add_message(packet_msg)
+            .add_attribute("method", "request_quote")
+            .add_attribute("rfq_id", rfq_id.to_string()))
+    }
+
+    pub fn submit_quote(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        rfq_id: u64,
+        price: Uint128,
+        deposit_transfer_channel_id: String,
+        ask_transfer_channel_id: String,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let rfq = RFQS_A
+            .may_load(deps.storage, rfq_id)?
+            .ok_or(ContractError::RequestClosed {})?;
+        if rfq.want.is_nft() {
+            return Err(ContractError::InvalidNft {});
+        }
+        let amount = match &rfq.want.denom {
+            Denom::Native(denom) => must_pay(&info, denom)?,
+            Denom::Cw20(_) => return Err(ContractError::Cw20Unsupported {}),
+        };
+        if amount != rfq.want.amount {
+            return Err(ContractError::InvalidQuote {});
+        }
+
+        let swap = Swap {
+            deposit: rfq.want,
+            deposit_address: info.sender,
+            deposit_transfer_channel_id,
+            ask: Token {
+                denom: rfq.pay_denom,
+                amount: price,
+                token_id: None,
+            },
+            ask_address: Some(rfq.requester),
+            ask_transfer_channel_id,
+            status: SwapStatus::Pending,
+            htlc: None,
+            taker: None,
+            dutch_auction: None,
+            english_auction: None,
+            rfq_id: Some(rfq_id),
+            recipient: None,
+            taker_recipient: None,
+            refund_address: None,
+            allowed_takers: None,
+            local: false,
+        };
+
+        list_swap(deps, env, state, swap, "submit_quote")
+    }
+
+    pub fn accept_quote(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let swap = swaps_b().load(deps.storage, id)?;
+        let rfq_id = swap.rfq_id.ok_or(ContractError::InvalidQuote {})?;
+        let rfq = RFQS_B
+            .may_load(deps.storage, rfq_id)?
+            .ok_or(ContractError::RequestClosed {})?;
+        if rfq.requester != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let close_msgs = close_request(deps.branch(), &env, rfq_id, Some(id))?;
+        let res = accept(deps, env, info, id, None)?;
+        Ok(res
+            .add_messages(close_msgs)
+            .add_attribute("rfq_id", rfq_id.to_string()))
+    }
+
+    pub fn cancel_request(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        rfq_id: u64,
+    ) -> Result<Response, ContractError> {
+        let rfq = RFQS_B
+            .may_load(deps.storage, rfq_id)?
+            .ok_or(ContractError::RequestClosed {})?;
+        if rfq.requester != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let close_msgs = close_request(deps, &env, rfq_id, None)?;
+        Ok(Response::new()
+            .add_messages(close_msgs)
+            .add_attribute("method", "cancel_request")
+            .add_attribute("rfq_id", rfq_id.to_string()))
+    }
+
+    /// Closes a request made on this chain. Its open quotes, but for
+    /// the accepted one, are withdrawn for side A to refund.
+    fn close_request(
+        deps: DepsMut,
+        env: &Env,
+        rfq_id: u64,
+        accepted: Option<u64>,
+    ) -> Result<Vec<IbcMsg>, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        let rfq = RFQS_B.load(deps.storage, rfq_id)?;
+        RFQS_B.remove(deps.storage, rfq_id);
+
+        let quotes = swaps_b()
+            .idx
+            .taker
+            .prefix(rfq.requester)
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let withdrawn = quotes.into_iter().filter(|(id, swap)| {
+            swap.rfq_id == Some(rfq_id) && swap.status == SwapStatus::Open && Some(*id) != accepted
+        });
+
+        let channel_id = state
+            .endpoint
+            .as_ref()
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id
+            .clone();
+        let timeout = env.block.time.plus_seconds(state.packet_lifetime);
+        let mut packets = vec![PacketMsg::CloseRequest { id: rfq_id }];
+        for (id, _) in withdrawn {
+            swaps_b().remove(deps.storage, id)?;
+            packets.push(PacketMsg::RefundSideA { id });
+        }
+        packets
+            .iter()
+            .map(|packet| {
+                Ok(IbcMsg::SendPacket {
+                    channel_id: channel_id.clone(),
+                    data: encode_packet(state.ibc_version.as_deref(), packet)?,
+                    timeout: timeout.into(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn accept(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        accept_with(deps, env, info, None, id, recipient)
+    }
+
+    /// Accepts a side B swap paid with the funds sent, or with `nft`
+    /// for swaps asking for one.
+    fn accept_with(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        nft: Option<Token>,
+        id: u64,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let mut swap = swaps_b().load(deps.storage, id)?;
+        if swap.status != SwapStatus::Open {
+            return Err(ContractError::SwapNotOpen {});
+        }
+        if swap.english_auction.is_some() {
+            return Err(ContractError::AuctionSwap {});
+        }
+        let recipient = recipient
+            .map(|recipient| validate_recipient(recipient, Some(&info.sender)))
+            .transpose()?;
+        if let Some(rfq_id) = swap.rfq_id {
+            if RFQS_B.has(deps.storage, rfq_id) {
+                return Err(ContractError::QuoteSwap {});
+            }
+        }
+        if let Some(htlc) = &swap.htlc {
+            if env.block.time >= htlc.taker_expires {
+                return Err(ContractError::SwapExpired {});
+            }
+        }
+
+        check_taker(deps.as_ref(), &swap, &info.sender)?;
+
+        let ask = swap.ask_at(env.block.time);
+        check_denom(deps.storage, &swap.deposit)?;
+        check_denom(deps.storage, &ask)?;
+        let deviation = check_auction_price(deps.as_ref(), &swap, &ask)?;
+        let paid = match (nft, &ask.denom) {
+            // The NFT is sent on to the maker through the bridge.
+            (Some(nft), _) if nft == ask => {
+                if NFT_BRIDGE.may_load(deps.storage)?.is_none() {
+                    return Err(ContractError::NoNftBridge {});
+                }
+                nft.amount
+            }
+            (Some(_), _) => return Err(ContractError::InvalidNft {}),
+            (None, _) if ask.is_nft() => return Err(ContractError::InvalidNft {}),
+            (None, Denom::Native(denom)) => must_pay(&info, denom)?,
+            (None, Denom::Cw20(_)) => return Err(ContractError::Cw20Unsupported {}),
+        };
+        if ask.amount > paid {
+            return Err(ContractError::InsufficientFunds {});
+        }
+
+        // The swap settles at its current price, so whatever the taker
+        // sent on top of it is returned.
+        let mut res = Response::new();
+        let price = swap.dutch_auction.as_ref().map(|_| ask.amount);
+        if paid > ask.amount {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![Token {
+                    denom: ask.denom.clone(),
+                    amount: paid - ask.amount,
+                    token_id: None,
+                }
+                .to_coin()?],
+            });
+        }
+
+        swap.ask = ask;
+        swap.status = SwapStatus::Accepted;
+        swap.taker = Some(info.sender.clone());
+        swap.taker_recipient = recipient;
+        swaps_b().save(deps.storage, id, &swap)?;
+        record_volume(deps.storage, VOLUME_IN, &swap.ask)?;
+        escrow_in(deps.storage, &swap.ask)?;
+
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let mut event = swap_event(
+            SWAP_ACCEPTED,
+            id,
+            "B",
+            &channel_id,
+            &swap,
+            Some(info.sender.as_str()),
+        );
+        if let Some(oracle_price) = deviation {
+            event = event.add_attribute("oracle_price", oracle_price.to_string());
+        }
+
+        let accept_msg = PacketMsg::AcceptSideA {
+            id,
+            sender: info.sender.to_string(),
+            price,
+            recipient: swap.taker_recipient.as_ref().map(Addr::to_string),
+        };
+
+        let packet_msg = IbcMsg::SendPacket {
+            channel_id,
+            data: encode_packet(state.ibc_version.as_deref(), &accept_msg)?,
+            timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
+        };
+
+        Ok(res
+            .add_message(packet_msg)
+            .add_attribute("method", "accept_swap")
+            .add_event(event))
+    }
+
+    /// Checks the price a Dutch auction is accepted at, which declined
+    /// from the one checked when it was listed.
+    fn check_auction_price(
+        deps: Deps,
+        swap: &Swap,
+        ask: &Token,
+    ) -> Result<Option<Decimal>, ContractError> {
+        match swap.dutch_auction {
+            Some(_) => check_price(deps, &swap.deposit, ask),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks that `taker` may accept the swap, if it is reserved for
+    /// its `ask_address` or restricted to `allowed_takers`.
+    fn check_taker(deps: Deps, swap: &Swap, taker: &Addr) -> Result<(), ContractError> {
+        if let Some(ask_address) = &swap.ask_address {
+            if taker != ask_address {
+                return Err(ContractError::Unauthorized {});
+            }
+        }
+        let allowed = match &swap.allowed_takers {
+            None => true,
+            Some(TakerAllowlist::Addresses(takers)) => takers.iter().any(|t| t == taker),
+            // A group that can not be queried admits nobody.
+            Some(TakerAllowlist::Cw4Group(group)) => Cw4Contract::new(Addr::unchecked(group))
+                .is_member(&deps.querier, taker, None)
+                .is_ok_and(|weight| weight.is_some()),
+        };
+        if !allowed {
+            return Err(ContractError::Unauthorized {});
+        }
+        Ok(())
+    }
+
+    /// Starts a Dutch auction declining from `ask` now.
+    fn schedule(
+        env: &Env,
+        ask: &Token,
+        msg: DutchAuctionMsg,
+    ) -> Result<DutchAuction, ContractError> {
+        if msg.floor >= ask.amount || msg.duration == 0 {
+            return Err(ContractError::InvalidAuction {});
+        }
+        Ok(DutchAuction {
+            floor: msg.floor,
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(msg.duration),
+        })
+    }
+
+    fn open_auction(env: &Env, msg: EnglishAuctionMsg) -> Result<EnglishAuction, ContractError> {
+        if msg.duration == 0 {
+            return Err(ContractError::InvalidAuction {});
+        }
+        Ok(EnglishAuction {
+            deadline: env.block.time.plus_seconds(msg.duration),
+            highest_bid: None,
+        })
+    }
+
+    pub fn bid(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let mut swap = swaps_b().load(deps.storage, id)?;
+        if swap.status != SwapStatus::Open {
+            return Err(ContractError::SwapNotOpen {});
+        }
+        check_taker(deps.as_ref(), &swap, &info.sender)?;
+        let auction = swap
+            .english_auction
+            .as_mut()
+            .ok_or(ContractError::NotAuction {})?;
+        if env.block.time >= auction.deadline {
+            return Err(ContractError::AuctionEnded {});
+        }
+
+        let amount = match &swap.ask.denom {
+            Denom::Native(denom) => must_pay(&info, denom)?,
+            Denom::Cw20(_) => return Err(ContractError::Cw20Unsupported {}),
+        };
+
+        // The first bid must meet the reserve, later ones beat the
+        // highest bid, whose bidder is paid back.
+        let mut res = Response::new();
+        match auction.highest_bid.take() {
+            Some(highest) => {
+                if amount <= highest.amount {
+                    return Err(ContractError::InsufficientFunds {});
+                }
+                let refund = Token {
+                    denom: swap.ask.denom.clone(),
+                    amount: highest.amount,
+                    token_id: None,
+                };
+                update_stats(deps.storage, |stats| stats.bids_refunded += 1)?;
+                record_volume(deps.storage, VOLUME_REFUNDED, &refund)?;
+                escrow_out(deps.storage, &refund)?;
+                res = res.add_message(send_token(&refund, highest.bidder.as_str())?);
+            }
+            None if amount < swap.ask.amount => {
+                return Err(ContractError::InsufficientFunds {});
+            }
+            None => {}
+        }
+        auction.highest_bid = Some(Bid {
+            bidder: info.sender.clone(),
+            amount,
+        });
+        swaps_b().save(deps.storage, id, &swap)?;
+
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let bid = Swap {
+            ask: Token {
+                denom: swap.ask.denom.clone(),
+                amount,
+                token_id: None,
+            },
+            ..swap
+        };
+        record_volume(deps.storage, VOLUME_IN, &bid.ask)?;
+        escrow_in(deps.storage, &bid.ask)?;
+        let event = swap_event(
+            SWAP_BID,
+            id,
+            "B",
+            &channel_id,
+            &bid,
+            Some(info.sender.as_str()),
+        );
+
+        Ok(res.add_attribute("method", "place_bid").add_event(event))
+    }
+
+    pub fn finalize(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let mut swap = swaps_b().load(deps.storage, id)?;
+        if swap.status != SwapStatus::Open {
+            return Err(ContractError::SwapNotOpen {});
+        }
+        let auction = swap
+            .english_auction
+            .as_ref()
+            .ok_or(ContractError::NotAuction {})?;
+        if env.block.time < auction.deadline {
+            return Err(ContractError::AuctionRunning {});
+        }
+
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let timeout = env.block.time.plus_seconds(state.packet_lifetime);
+        let res = Response::new().add_attribute("method", "finalize_auction");
+
+        // Without bids the listing is withdrawn and side A refunds.
+        let bid = match auction.highest_bid.clone() {
+            Some(bid) => bid,
+            None => {
+                swaps_b().remove(deps.storage, id)?;
+                let packet_msg = IbcMsg::SendPacket {
+                    channel_id,
+                    data: encode_packet(
+                        state.ibc_version.as_deref(),
+                        &PacketMsg::RefundSideA { id },
+                    )?,
+                    timeout: timeout.into(),
+                };
+                return Ok(res.add_message(packet_msg));
+            }
+        };
+
+        // The winning bid settles like an accepted swap, paid out to
+        // the maker once side A acknowledges the accept. It was already
+        // escrowed when placed.
+        swap.ask.amount = bid.amount;
+        swap.status = SwapStatus::Accepted;
+        swap.taker = Some(bid.bidder.clone());
+        swaps_b().save(deps.storage, id, &swap)?;
+
+        let event = swap_event(SWAP_ACCEPTED, id, "B", &channel_id, &swap, None);
+        let accept_msg = PacketMsg::AcceptSideA {
+            id,
+            sender: bid.bidder.to_string(),
+            price: Some(bid.amount),
+            recipient: None,
+        };
+        let packet_msg = IbcMsg::SendPacket {
+            channel_id,
+            data: encode_packet(state.ibc_version.as_deref(), &accept_msg)?,
+            timeout: timeout.into(),
+        };
+
+        Ok(res.add_message(packet_msg).add_event(event))
+    }
+
+    /// Derives both sides' expiries from the maker's time lock. Each
+    /// half must outlast a packet so the accept and the preimage can be
+    /// relayed in time.
+    fn lock(env: &Env, state: &State, msg: HtlcMsg) -> Result<Htlc, ContractError> {
+        if msg.hashlock.len() != 32 {
+            return Err(ContractError::InvalidHashlock {});
+        }
+        let min = 2 * state.packet_lifetime;
+        if msg.timelock < min {
+            return Err(ContractError::InvalidTimelock { min });
+        }
+        Ok(Htlc {
+            hashlock: msg.hashlock,
+            maker_expires: env.block.time.plus_seconds(msg.timelock),
+            taker_expires: env.block.time.plus_seconds(msg.timelock / 2),
+        })
+    }
+
+    pub fn reveal(
+        deps: DepsMut,
+        env: Env,
+        id: u64,
+        preimage: HexBinary,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let swap = swaps_b().load(deps.storage, id)?;
+        let htlc = swap.htlc.as_ref().ok_or(ContractError::NotHtlc {})?;
+        if swap.status != SwapStatus::Accepted {
+            return Err(ContractError::SwapNotAccepted {});
+        }
+        if env.block.time >= htlc.taker_expires {
+            return Err(ContractError::SwapExpired {});
+        }
+        if !htlc.verify(&preimage) {
+            return Err(ContractError::InvalidPreimage {});
+        }
+
+        swaps_b().remove(deps.storage, id)?;
+        update_stats(deps.storage, |stats| stats.swaps_settled += 1)?;
+        record_volume(deps.storage, VOLUME_OUT, &swap.ask)?;
+        escrow_out(deps.storage, &swap.ask)?;
+
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let event = swap_event(SWAP_SETTLED, id, "B", &channel_id, &swap, None);
+        let timeout = env.block.time.plus_seconds(state.packet_lifetime);
+        let hook = SwapHookMsg::SwapSettled {
+            id,
+            side: "B".to_string(),
+            swap: swap.clone(),
+        };
+
+        let transfer_msg = transfer_token(
+            deps.storage,
+            &swap.ask,
+            &swap.ask_transfer_channel_id,
+            swap.maker_payee().as_str(),
+            timeout,
+        )?;
+        let packet_msg = IbcMsg::SendPacket {
+            channel_id,
+            data: encode_packet(
+                state.ibc_version.as_deref(),
+                &PacketMsg::RevealSideA { id, preimage },
+            )?,
+            timeout: timeout.into(),
+        };
+
+        Ok(Response::new()
+            .add_message(transfer_msg)
+            .add_message(packet_msg)
+            .add_attribute("method", "reveal_preimage")
+            .add_event(event)
+            .add_submessages(swap_hooks(deps.storage, hook)?))
+    }
+
+    pub fn claim(
+        deps: DepsMut,
+        env: Env,
+        id: u64,
+        preimage: HexBinary,
+    ) -> Result<Response, ContractError> {
+        let (transfer_msg, event, hooks) = release_deposit(deps, &env, id, &preimage)?;
+        Ok(Response::new()
+            .add_message(transfer_msg)
+            .add_attribute("method", "claim_swap")
+            .add_event(event)
+            .add_submessages(hooks))
+    }
+
+    /// Releases the deposit of an accepted HTLC swap on side A to its
+    /// taker, given the preimage of its hash lock.
+    pub fn release_deposit(
+        deps: DepsMut,
+        env: &Env,
+        id: u64,
+        preimage: &[u8],
+    ) -> Result<(CosmosMsg, Event, Vec<SubMsg>), ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let swap = swaps_a().load(deps.storage, id)?;
+        let htlc = swap.htlc.as_ref().ok_or(ContractError::NotHtlc {})?;
+        let taker = match (&swap.status, swap.taker_payee()) {
+            (SwapStatus::Accepted, Some(taker)) => taker,
+            _ => return Err(ContractError::SwapNotAccepted {}),
+        };
+        if env.block.time >= htlc.maker_expires {
+            return Err(ContractError::SwapExpired {});
+        }
+        if !htlc.verify(preimage) {
+            return Err(ContractError::InvalidPreimage {});
+        }
+
+        swaps_a().remove(deps.storage, id)?;
+        update_stats(deps.storage, |stats| stats.swaps_settled += 1)?;
+        record_volume(deps.storage, VOLUME_OUT, &swap.deposit)?;
+        escrow_out(deps.storage, &swap.deposit)?;
+
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let event = swap_event(SWAP_SETTLED, id, "A", &channel_id, &swap, None);
+        let transfer_msg = transfer_token(
+            deps.storage,
+            &swap.deposit,
+            &swap.deposit_transfer_channel_id,
+            taker.as_str(),
+            env.block.time.plus_seconds(state.packet_lifetime),
+        )?;
+        let hook = SwapHookMsg::SwapSettled {
+            id,
+            side: "A".to_string(),
+            swap,
+        };
+        Ok((transfer_msg, event, swap_hooks(deps.storage, hook)?))
+    }
+
+    pub fn refund(
+        deps: DepsMut,
+        env: Env,
+        side: String,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        let swaps = swaps_for_side(&side)?;
+        let swap = swaps.load(deps.storage, id)?;
+        let htlc = swap.htlc.as_ref().ok_or(ContractError::NotHtlc {})?;
+
+        // Side A holds the maker's deposit, side B the taker's payment.
+        let (expires, owner, token) = if side == "A" {
+            (htlc.maker_expires, swap.refund_payee(), &swap.deposit)
+        } else {
+            match (&swap.status, &swap.taker) {
+                (SwapStatus::Accepted, Some(taker)) => (htlc.taker_expires, taker, &swap.ask),
+                _ => return Err(ContractError::SwapNotAccepted {}),
+            }
+        };
+        if env.block.time < expires {
+            return Err(ContractError::SwapNotExpired {});
+        }
+
+        swaps.remove(deps.storage, id)?;
+        update_stats(deps.storage, |stats| stats.swaps_refunded += 1)?;
+        record_volume(deps.storage, VOLUME_REFUNDED, token)?;
+        escrow_out(deps.storage, token)?;
+
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let event = swap_event(SWAP_REFUNDED, id, &side, &channel_id, &swap, None);
+        let refund_msg = send_token(token, owner.as_str())?;
+        let hook = SwapHookMsg::SwapCancelled { id, side, swap };
+
+        Ok(Response::new()
+            .add_message(refund_msg)
+            .add_attribute("method", "refund_swap")
+            .add_event(event)
+            .add_submessages(swap_hooks(deps.storage, hook)?))
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetSwap { side, id } => to_binary(&query::get_swap(deps, side, id)?),
+        QueryMsg::CurrentAsk { side, id } => to_binary(&query::current_ask(deps, env, side, id)?),
+        QueryMsg::Config {} => to_binary(&query::config(deps)?),
+        QueryMsg::Stats {} => to_binary(&query::stats(deps)?),
+        QueryMsg::Solvency {} => to_binary(&query::solvency(deps, env)?),
+        QueryMsg::DenomRules { start_after, limit } => {
+            to_binary(&query::denom_rules(deps, start_after, limit)?)
+        }
+        QueryMsg::PriceOracle {} => to_binary(&PRICE_ORACLE.may_load(deps.storage)?),
+        QueryMsg::NftBridge {} => to_binary(&NFT_BRIDGE.may_load(deps.storage)?),
+        QueryMsg::NftCollections {} => to_binary(&query::nft_collections(deps)?),
+        QueryMsg::PositionNft {} => to_binary(&POSITION_NFT.may_load(deps.storage)?),
+        QueryMsg::Hooks {} => to_binary(&HOOKS.query_hooks(deps)?),
+        QueryMsg::HookFee {} => to_binary(&HOOK_FEE.may_load(deps.storage)?),
+        QueryMsg::HookGasLimit {} => to_binary(
+            &HOOK_GAS_LIMIT
+                .may_load(deps.storage)?
+                .unwrap_or(DEFAULT_HOOK_GAS_LIMIT),
+        ),
+        QueryMsg::RateLimit { channel_id, denom } => {
+            to_binary(&query::rate_limit(deps, env, channel_id, denom)?)
+        }
+        QueryMsg::QueuedPayouts { start_after, limit } => {
+            to_binary(&query::queued_payouts(deps, start_after, limit)?)
+        }
+        QueryMsg::ContractInfo {} => to_binary(&query::contract_info(deps)?),
+        QueryMsg::AuthorizedChannels {} => to_binary(&query::authorized_channels(deps)?),
+        QueryMsg::ListSwaps {
+            side,
+            start_after,
+            limit,
+            maker,
+            deposit_denom,
+            ask_denom,
+            status,
+        } => to_binary(&query::list_swaps(
+            deps,
+            side,
+            start_after,
+            limit,
+            query::SwapFilter {
+                maker,
+                taker: None,
+                deposit_denom,
+                ask_denom,
+                status,
+                rfq_id: None,
+            },
+        )?),
+        QueryMsg::ListRequests {
+            side,
+            start_after,
+            limit,
+        } => to_binary(&query::list_requests(deps, side, start_after, limit)?),
+        QueryMsg::Quotes {
+            rfq_id,
+            start_after,
+            limit,
+        } => to_binary(&query::quotes(deps, rfq_id, start_after, limit)?),
+        QueryMsg::SwapsByMaker {
+            side,
+            maker,
+            start_after,
+            limit,
+        } => to_binary(&query::list_swaps(
+            deps,
+            side,
+            start_after,
+            limit,
+            query::SwapFilter {
+                maker: Some(maker),
+                ..Default::default()
+            },
+        )?),
+        QueryMsg::SwapsByTaker {
+            side,
+            taker,
+            start_after,
+            limit,
+        } => to_binary(&query::list_swaps(
+            deps,
+            side,
+            start_after,
+            limit,
+            query::SwapFilter {
+                taker: Some(taker),
+                ..Default::default()
+            },
+        )?),
+        QueryMsg::SwapsByDenomPair {
+            side,
+            deposit_denom,
+            ask_denom,
+            start_after,
+            limit,
+        } => to_binary(&query::list_swaps(
+            deps,
+            side,
+            start_after,
+            limit,
+            query::SwapFilter {
+                deposit_denom: Some(deposit_denom),
+                ask_denom: Some(ask_denom),
+                ..Default::default()
+            },
+        )?),
+    }
+}
+
+pub mod query {
+    use std::collections::BTreeMap;
+
+    use cosmwasm_std::{Addr, StdError};
+    use cw_storage_plus::Bound;
+
+    use crate::ibc::{IBC_VERSION, IBC_VERSION_PREFIX, MAX_IBC_REVISION, MIN_IBC_REVISION};
+    use crate::msg::{
+        AuthorizedChannel, AuthorizedChannelsResponse, ContractInfoResponse, DenomRuleItem,
+        DenomRulesResponse, DenomSolvency, DenomVolume, ListRequestsResponse, ListSwapsResponse,
+        NftCollectionsResponse, RateLimitResponse, RfqItem, SolvencyResponse, StatsResponse,
+        SwapItem,
+    };
+    use crate::state::{denom_key, swaps_for_side, ESCROW, FLOWS, STATS, VOLUME};
+
+    use super::*;
+
+    const DEFAULT_LIMIT: u32 = 10;
+    const MAX_LIMIT: u32 = 30;
+
+    /// Optional constraints applied by `ListSwaps`.
+    #[derive(Default)]
+    pub struct SwapFilter {
+        pub maker: Option<String>,
+        pub taker: Option<String>,
+        pub deposit_denom: Option<String>,
+        pub ask_denom: Option<String>,
+        pub status: Option<SwapStatus>,
+        pub rfq_id: Option<u64>,
+    }
+
+    impl SwapFilter {
+        fn matches(&self, swap: &Swap) -> bool {
+            if let Some(maker) = &self.maker {
+                if swap.deposit_address != *maker {
+                    return false;
+                }
+            }
+            if let Some(taker) = &self.taker {
+                if swap.ask_address.as_ref() != Some(&Addr::unchecked(taker)) {
+                    return false;
+                }
+            }
+            if let Some(denom) = &self.deposit_denom {
+                if denom_key(&swap.deposit.denom) != *denom {
+                    return false;
+                }
+            }
+            if let Some(denom) = &self.ask_denom {
+                if denom_key(&swap.ask.denom) != *denom {
+                    return false;
+                }
+            }
+            if let Some(status) = &self.status {
+                if swap.status != *status {
+                    return false;
+                }
+            }
+            if self.rfq_id.is_some() && swap.rfq_id != self.rfq_id {
+                return false;
+            }
+            true
+        }
+    }
+
+    pub fn config(deps: Deps) -> StdResult<State> {
+        STATE.load(deps.storage)
+    }
+
+    pub fn stats(deps: Deps) -> StdResult<StatsResponse> {
+        let stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+        let volume = VOLUME
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(|((direction, denom), amount)| DenomVolume {
+                    direction,
+                    denom,
+                    amount,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(StatsResponse { stats, volume })
+    }
+
+    pub fn solvency(deps: Deps, env: Env) -> StdResult<SolvencyResponse> {
+        let mut denoms = BTreeMap::<String, DenomSolvency>::new();
+        for item in ESCROW.range(deps.storage, None, None, Order::Ascending) {
+            let (denom, owed) = item?;
+            denoms.insert(
+                denom.clone(),
+                DenomSolvency {
+                    denom,
+                    owed,
+                    balance: Uint128::zero(),
+                },
+            );
+        }
+        for balance in deps.querier.query_all_balances(env.contract.address)? {
+            denoms
+                .entry(balance.denom.clone())
+                .or_insert_with(|| DenomSolvency {
+                    denom: balance.denom,
+                    owed: Uint128::zero(),
+                    balance: Uint128::zero(),
+                })
+                .balance = balance.amount;
+        }
+        let denoms: Vec<_> = denoms.into_values().collect();
+        Ok(SolvencyResponse {
+            solvent: denoms.iter().all(|denom| denom.balance >= denom.owed),
+            denoms,
+        })
+    }
+
+    pub fn contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
+        let version = get_contract_version(deps.storage)?;
+        let state = STATE.load(deps.storage)?;
+        Ok(ContractInfoResponse {
+            contract: version.contract,
+            version: version.version,
+            ibc_version: IBC_VERSION.to_string(),
+            supported_ibc_versions: (MIN_IBC_REVISION..=MAX_IBC_REVISION)
+                .map(|revision| format!("{}{}", IBC_VERSION_PREFIX, revision))
+                .collect(),
+            channel_version: state.ibc_version,
+            features: FEATURES.iter().map(|f| f.to_string()).collect(),
+        })
+    }
+
+    pub fn get_swap(deps: Deps, side: String, id: u64) -> StdResult<Swap> {
+        swaps_for_side(&side)?.load(deps.storage, id)
+    }
+
+    pub fn current_ask(deps: Deps, env: Env, side: String, id: u64) -> StdResult<Token> {
+        let swap = swaps_for_side(&side)?.load(deps.storage, id)?;
+        Ok(swap.ask_at(env.block.time))
+    }
+
+    pub fn authorized_channels(deps: Deps) -> StdResult<AuthorizedChannelsResponse> {
+        let channels = AUTHORIZED_CHANNELS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(|(connection_id, counterparty_port_id)| AuthorizedChannel {
+                    connection_id,
+                    counterparty_port_id,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(AuthorizedChannelsResponse { channels })
+    }
+
+    pub fn nft_collections(deps: Deps) -> StdResult<NftCollectionsResponse> {
+        let collections = NFT_COLLECTIONS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        Ok(NftCollectionsResponse { collections })
+    }
+
+    pub fn denom_rules(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<DenomRulesResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.as_deref().map(Bound::exclusive);
+        let rules = DENOM_RULES
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(denom, rule)| DenomRuleItem { denom, rule }))
+            .collect::<StdResult<_>>()?;
+        Ok(DenomRulesResponse {
+            allowlist: DENOM_ALLOWLIST.may_load(deps.storage)?.unwrap_or_default(),
+            rules,
+        })
+    }
+
+    pub fn rate_limit(
+        deps: Deps,
+        env: Env,
+        channel_id: String,
+        denom: String,
+    ) -> StdResult<RateLimitResponse> {
+        let limit = RATE_LIMITS.may_load(deps.storage, (&channel_id, &denom))?;
+        let total = |direction: &str| -> StdResult<Uint128> {
+            let key = (direction, channel_id.as_str(), denom.as_str());
+            Ok(match (&limit, FLOWS.may_load(deps.storage, key)?) {
+                (Some(limit), Some(mut flow)) => flow.advance(limit, env.block.time),
+                _ => Uint128::zero(),
+            })
+        };
+        Ok(RateLimitResponse {
+            inbound: total(VOLUME_IN)?,
+            outbound: total(VOLUME_OUT)?,
+            limit,
+        })
+    }
+
+    pub fn queued_payouts(
+        deps: Deps,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<ListSwapsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let swaps = PAYOUTS
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                item.map(|(id, payout)| SwapItem {
+                    id,
+                    swap: payout.swap,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(ListSwapsResponse { swaps })
+    }
+
+    pub fn list_requests(
+        deps: Deps,
+        side: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<ListRequestsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let rfqs = if side == "A" {
+            RFQS_A
+        } else if side == "B" {
+            RFQS_B
         } else {
             return Err(StdError::generic_err("Invalid side"));
+        };
+        let requests = rfqs
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(id, rfq)| RfqItem { id, rfq }))
+            .collect::<StdResult<_>>()?;
+        Ok(ListRequestsResponse { requests })
+    }
+
+    pub fn quotes(
+        deps: Deps,
+        rfq_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<ListSwapsResponse> {
+        let rfq = RFQS_B.load(deps.storage, rfq_id)?;
+        list_swaps(
+            deps,
+            "B".to_string(),
+            start_after,
+            limit,
+            SwapFilter {
+                taker: Some(rfq.requester.to_string()),
+                rfq_id: Some(rfq_id),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn list_swaps(
+        deps: Deps,
+        side: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        filter: SwapFilter,
+    ) -> StdResult<ListSwapsResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+        let swaps = swaps_for_side(&side)?;
+
+        // Walk the most selective index available and check the
+        // remaining constraints on each entry it yields. Makers
+        // are addresses on side A's chain, so they are not validated
+        // against this chain's prefix.
+        let range = if let Some(maker) = &filter.maker {
+            swaps.idx.maker.prefix(Addr::unchecked(maker)).range(
+                deps.storage,
+                start,
+                None,
+                Order::Ascending,
+            )
+        } else if let Some(taker) = &filter.taker {
+            swaps.idx.taker.prefix(Addr::unchecked(taker)).range(
+                deps.storage,
+                start,
+                None,
+                Order::Ascending,
+            )
+        } else if let (Some(deposit_denom), Some(ask_denom)) =
+            (&filter.deposit_denom, &filter.ask_denom)
+        {
+            swaps
+                .idx
+                .denom_pair
+                .prefix((deposit_denom.clone(), ask_denom.clone()))
+                .range(deps.storage, start, None, Order::Ascending)
+        } else if let Some(denom) = &filter.deposit_denom {
+            swaps.idx.deposit_denom.prefix(denom.clone()).range(
+                deps.storage,
+                start,
+                None,
+                Order::Ascending,
+            )
+        } else if let Some(denom) = &filter.ask_denom {
+            swaps.idx.ask_denom.prefix(denom.clone()).range(
+                deps.storage,
+                start,
+                None,
+                Order::Ascending,
+            )
+        } else if let Some(status) = &filter.status {
+            swaps.idx.status.prefix(status.as_str().to_string()).range(
+                deps.storage,
+                start,
+                None,
+                Order::Ascending,
+            )
+        } else {
+            swaps.range(deps.storage, start, None, Order::Ascending)
+        };
+
+        let swaps = range
+            .filter(|item| item.as_ref().map_or(true, |(_, swap)| filter.matches(swap)))
+            .take(limit)
+            .map(|item| item.map(|(id, swap)| SwapItem { id, swap }))
+            .collect::<StdResult<_>>()?;
+
+        Ok(ListSwapsResponse { swaps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bech32::{ToBase32, Variant};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_ibc_channel, mock_ibc_packet_ack, mock_ibc_packet_recv,
+        mock_ibc_packet_timeout, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{
+        coin, coins, from_binary, Addr, ContractResult, CosmosMsg, IbcAcknowledgement, IbcChannel,
+        IbcChannelConnectMsg, IbcChannelOpenMsg, IbcEndpoint, IbcOrder, OwnedDeps, ReplyOn,
+        SubMsgResponse, SubMsgResult, SystemError, SystemResult, WasmQuery,
+    };
+    use cw_utils::PaymentError;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::ibc::{
+        ibc_channel_connect, ibc_channel_open, ibc_packet_ack, ibc_packet_receive,
+        ibc_packet_timeout, IBC_VERSION,
+    };
+    use crate::ibc_helpers::StdAck;
+    use crate::msg::{
+        AcceptAck, AuthorizedChannel, AuthorizedChannelsResponse, ContractInfoResponse,
+        DenomRuleItem, DenomRulesResponse, DenomSolvency, DenomVolume, ListRequestsResponse,
+        ListSwapsResponse, NftCollectionsResponse, PacketEnvelope, RateLimitResponse,
+        SolvencyResponse, StatsResponse,
+    };
+
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        setup_on(IBC_VERSION)
+    }
+
+    /// Like `setup`, with the counterparty settling on `version`.
+    fn setup_on(version: &str) -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            InstantiateMsg {
+                packet_lifetime: 60,
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::AuthorizeChannel {
+                connection_id: "connection-2".to_string(),
+                counterparty_port_id: "their-port".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            mock_env(),
+            IbcChannelConnectMsg::new_ack(channel(), version),
+        )
+        .unwrap();
+        deps
+    }
+
+    /// Channel bound by `setup`, whose counterparty end sends the
+    /// packets of `mock_ibc_packet_recv`.
+    fn channel() -> IbcChannel {
+        let mut channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, IBC_VERSION);
+        channel.counterparty_endpoint = IbcEndpoint {
+            port_id: "their-port".to_string(),
+            channel_id: "channel-1234".to_string(),
+        };
+        channel
+    }
+
+    fn token(denom: &str, amount: u128) -> Token {
+        Token {
+            denom: Denom::Native(denom.to_string()),
+            amount: Uint128::new(amount),
+            token_id: None,
+        }
+    }
+
+    fn receive_swap(deps: DepsMut, id: u64, maker: &str, deposit: &str, ask: &str) {
+        receive_reserved_swap(deps, id, maker, deposit, ask, None)
+    }
+
+    fn receive_reserved_swap(
+        deps: DepsMut,
+        id: u64,
+        maker: &str,
+        deposit: &str,
+        ask: &str,
+        taker: Option<&str>,
+    ) {
+        let packet = PacketEnvelope {
+            version: IBC_VERSION.to_string(),
+            msg: PacketMsg::CreateSideB {
+                id,
+                swap: Swap {
+                    deposit: token(deposit, 100),
+                    deposit_address: Addr::unchecked(maker),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask: token(ask, 200),
+                    ask_address: taker.map(Addr::unchecked),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    status: SwapStatus::Pending,
+                    htlc: None,
+                    taker: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    rfq_id: None,
+                    recipient: None,
+                    taker_recipient: None,
+                    refund_address: None,
+                    allowed_takers: None,
+                    local: false,
+                },
+            },
+        };
+        ibc_packet_receive(
+            deps,
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &packet).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn list_ids(deps: Deps, msg: QueryMsg) -> Vec<u64> {
+        let res: ListSwapsResponse = from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+        res.swaps.into_iter().map(|item| item.id).collect()
+    }
+
+    fn list_b(start_after: Option<u64>, limit: Option<u32>) -> QueryMsg {
+        QueryMsg::ListSwaps {
+            side: "B".to_string(),
+            start_after,
+            limit,
+            maker: None,
+            deposit_denom: None,
+            ask_denom: None,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn test_list_swaps_with_filters() {
+        let mut deps = setup();
+        receive_swap(deps.as_mut(), 0, "alice", "ujuno", "uosmo");
+        receive_swap(deps.as_mut(), 1, "bob", "ujuno", "uatom");
+        receive_swap(deps.as_mut(), 2, "alice", "uatom", "uosmo");
+        receive_swap(deps.as_mut(), 3, "alice", "ujuno", "uosmo");
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 3,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            list_ids(deps.as_ref(), list_b(None, None)),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(
+            list_ids(deps.as_ref(), list_b(Some(0), Some(2))),
+            vec![1, 2]
+        );
+
+        let mut msg = list_b(None, None);
+        if let QueryMsg::ListSwaps {
+            maker, ask_denom, ..
+        } = &mut msg
+        {
+            *maker = Some("alice".to_string());
+            *ask_denom = Some("uosmo".to_string());
+        }
+        assert_eq!(list_ids(deps.as_ref(), msg), vec![0, 2, 3]);
+
+        let mut msg = list_b(None, None);
+        if let QueryMsg::ListSwaps {
+            deposit_denom,
+            status,
+            ..
+        } = &mut msg
+        {
+            *deposit_denom = Some("ujuno".to_string());
+            *status = Some(SwapStatus::Open);
+        }
+        assert_eq!(list_ids(deps.as_ref(), msg), vec![0, 1]);
+
+        let mut msg = list_b(Some(0), Some(1));
+        if let QueryMsg::ListSwaps { status, .. } = &mut msg {
+            *status = Some(SwapStatus::Accepted);
+        }
+        assert_eq!(list_ids(deps.as_ref(), msg), vec![3]);
+
+        // An accepted swap can not be accepted a second time.
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 3,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SwapNotOpen {}));
+    }
+
+    #[test]
+    fn test_swaps_by_maker_taker_and_pair() {
+        let mut deps = setup();
+        receive_swap(deps.as_mut(), 0, "alice", "ujuno", "uosmo");
+        receive_reserved_swap(deps.as_mut(), 1, "bob", "ujuno", "uosmo", Some("carol"));
+        receive_reserved_swap(deps.as_mut(), 2, "alice", "uosmo", "ujuno", Some("carol"));
+
+        let by_maker = QueryMsg::SwapsByMaker {
+            side: "B".to_string(),
+            maker: "alice".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        assert_eq!(list_ids(deps.as_ref(), by_maker), vec![0, 2]);
+
+        let by_taker = QueryMsg::SwapsByTaker {
+            side: "B".to_string(),
+            taker: "carol".to_string(),
+            start_after: Some(1),
+            limit: None,
+        };
+        assert_eq!(list_ids(deps.as_ref(), by_taker), vec![2]);
+
+        let by_pair = QueryMsg::SwapsByDenomPair {
+            side: "B".to_string(),
+            deposit_denom: "ujuno".to_string(),
+            ask_denom: "uosmo".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        assert_eq!(list_ids(deps.as_ref(), by_pair), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_config_and_contract_info() {
+        let deps = setup();
+
+        let config: State =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.owner, "owner");
+        assert_eq!(config.packet_lifetime, 60);
+        assert_eq!(config.endpoint.unwrap().channel_id, "channel-0");
+
+        let info: ContractInfoResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::ContractInfo {}).unwrap())
+                .unwrap();
+        assert_eq!(info.contract, CONTRACT_NAME);
+        assert_eq!(info.version, CONTRACT_VERSION);
+        assert_eq!(info.ibc_version, IBC_VERSION);
+        assert_eq!(
+            info.supported_ibc_versions,
+            vec!["native-swap-1", "native-swap-2"]
+        );
+        assert_eq!(info.channel_version.as_deref(), Some(IBC_VERSION));
+        assert!(info.features.contains(&"list_swaps".to_string()));
+    }
+
+    #[test]
+    fn test_execute_before_connect() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            InstantiateMsg {
+                packet_lifetime: 60,
+            },
+        )
+        .unwrap();
+
+        // Swaps across chains need the channel, local ones do not.
+        let create = |local: bool| ExecuteMsg::CreateSwap {
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask_transfer_channel_id: "channel-2".to_string(),
+            htlc: None,
+            dutch_auction: None,
+            english_auction: None,
+            recipient: None,
+            refund_address: None,
+            allowed_takers: None,
+            local,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            create(false),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NotConnected {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            create(true),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_legacy_channel_listings() {
+        let mut deps = setup_on("native-swap-1");
+        let create = |deps: DepsMut, recipient, allowed_takers| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker", &coins(100, "ujuno")),
+                ExecuteMsg::CreateSwap {
+                    ask: token("uosmo", 200),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    recipient,
+                    refund_address: None,
+                    allowed_takers,
+                    local: false,
+                },
+            )
+        };
+
+        // Plain swaps are listed as the bare packets 0.1.x decodes.
+        let res = create(deps.as_mut(), None, None).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => assert!(matches!(
+                from_binary(data).unwrap(),
+                PacketMsg::CreateSideB { id: 0, .. }
+            )),
+            msg => panic!("unexpected message {:?}", msg),
+        }
+
+        // Terms the peer would drop are refused instead.
+        let err = create(deps.as_mut(), Some(bech32_addr("juno", 1)), None).unwrap_err();
+        assert!(matches!(err, ContractError::LegacyChannel {}));
+        let takers = TakerAllowlist::Addresses(vec!["taker".to_string()]);
+        let err = create(deps.as_mut(), None, Some(takers)).unwrap_err();
+        assert!(matches!(err, ContractError::LegacyChannel {}));
+        assert!(!swaps_a().has(&deps.storage, 1));
+
+        // Quotes are reserved for their requester, which 0.1.x can not
+        // enforce.
+        let request = PacketMsg::RequestQuote {
+            id: 0,
+            rfq: Rfq {
+                requester: Addr::unchecked("requester"),
+                want: token("ujuno", 100),
+                pay_denom: Denom::Native("uosmo".to_string()),
+            },
+        };
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &request).unwrap(),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::SubmitQuote {
+                rfq_id: 0,
+                price: Uint128::new(200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::LegacyChannel {}));
+        assert_eq!(SWAP_ID.load(&deps.storage).unwrap(), 1);
+    }
+
+    fn envelope(msg: PacketMsg) -> PacketEnvelope {
+        PacketEnvelope {
+            version: IBC_VERSION.to_string(),
+            msg,
+        }
+    }
+
+    fn create_swap(deps: DepsMut, maker: &str, deposit: &str, amount: u128) {
+        execute(
+            deps,
+            mock_env(),
+            mock_info(maker, &coins(amount, deposit)),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap();
+    }
+
+    fn accept_swap(deps: DepsMut, taker: &str, id: u64) {
+        execute(
+            deps,
+            mock_env(),
+            mock_info(taker, &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap();
+    }
+
+    fn stats(deps: Deps) -> StatsResponse {
+        from_binary(&query(deps, mock_env(), QueryMsg::Stats {}).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_stats_track_swap_lifecycle() {
+        // Side A: the maker escrows a deposit that is released on accept.
+        let mut deps = setup();
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        create_swap(deps.as_mut(), "maker", "ujuno", 50);
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv(
+                "channel-0",
+                &envelope(PacketMsg::AcceptSideA {
+                    id: 0,
+                    sender: "taker".to_string(),
+                    price: None,
+                    recipient: None,
+                }),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let res = stats(deps.as_ref());
+        assert_eq!(res.stats.swaps_created, 2);
+        assert_eq!(res.stats.swaps_settled, 1);
+        assert_eq!(
+            res.volume,
+            vec![
+                DenomVolume {
+                    direction: "in".to_string(),
+                    denom: "ujuno".to_string(),
+                    amount: Uint128::new(150),
+                },
+                DenomVolume {
+                    direction: "out".to_string(),
+                    denom: "ujuno".to_string(),
+                    amount: Uint128::new(100),
+                },
+            ]
+        );
+
+        // A listing side B refuses is refunded, which is not outbound
+        // volume.
+        let swap = swaps_a().load(&deps.storage, 1).unwrap();
+        ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 1, swap }),
+                IbcAcknowledgement::new(StdAck::fail("refused".to_string())),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let res = stats(deps.as_ref());
+        assert_eq!(res.stats.swaps_refunded, 1);
+        assert_eq!(res.stats.swaps_cancelled, 0);
+        assert_eq!(
+            res.volume[1..],
+            [
+                DenomVolume {
+                    direction: "out".to_string(),
+                    denom: "ujuno".to_string(),
+                    amount: Uint128::new(100),
+                },
+                DenomVolume {
+                    direction: "refunded".to_string(),
+                    denom: "ujuno".to_string(),
+                    amount: Uint128::new(50),
+                },
+            ]
+        );
+
+        // A swap its maker cancels is counted as cancelled instead.
+        create_swap(deps.as_mut(), "maker", "ujuno", 25);
+        let swap = swaps_a().load(&deps.storage, 2).unwrap();
+        ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 2, swap }),
+                IbcAcknowledgement::new(StdAck::success(2)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &[]),
+            ExecuteMsg::CancelSwap { id: 2 },
+        )
+        .unwrap();
+        ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::CancelSideB { id: 2 }),
+                IbcAcknowledgement::new(StdAck::success(2)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let res = stats(deps.as_ref());
+        assert_eq!(res.stats.swaps_created, 3);
+        assert_eq!(res.stats.swaps_refunded, 1);
+        assert_eq!(res.stats.swaps_cancelled, 1);
+        assert_eq!(res.volume[2].amount, Uint128::new(75));
+
+        // Side B counts the listing it withdrew for the maker.
+        let mut deps = setup();
+        receive_swap(deps.as_mut(), 0, "maker", "ujuno", "uosmo");
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &envelope(PacketMsg::CancelSideB { id: 0 })).unwrap(),
+        )
+        .unwrap();
+        let res = stats(deps.as_ref());
+        assert_eq!(res.stats.swaps_cancelled, 1);
+        assert_eq!(res.stats.swaps_refunded, 0);
+
+        // Side B: the taker pays the ask, which is released on the ack.
+        let mut deps = setup();
+        receive_swap(deps.as_mut(), 0, "maker", "ujuno", "uosmo");
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap();
+        ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::AcceptSideA {
+                    id: 0,
+                    sender: "taker".to_string(),
+                    price: None,
+                    recipient: None,
+                }),
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let res = stats(deps.as_ref());
+        assert_eq!(res.stats.swaps_created, 0);
+        assert_eq!(res.stats.swaps_settled, 1);
+        assert_eq!(res.volume.len(), 2);
+        assert!(res
+            .volume
+            .iter()
+            .all(|v| v.denom == "uosmo" && v.amount.u128() == 200));
+    }
+
+    #[test]
+    fn test_swap_events() {
+        let mut deps = setup();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.events.len(), 1);
+        let event = &res.events[0];
+        assert_eq!(event.ty, "swap_created");
+        let attr = |key: &str| {
+            event
+                .attributes
+                .iter()
+                .find(|attr| attr.key == key)
+                .map(|attr| attr.value.as_str())
+        };
+        assert_eq!(attr("id"), Some("0"));
+        assert_eq!(attr("side"), Some("A"));
+        assert_eq!(attr("channel"), Some("channel-0"));
+        assert_eq!(attr("maker"), Some("maker"));
+        assert_eq!(attr("taker"), None);
+        assert_eq!(attr("deposit_denom"), Some("ujuno"));
+        assert_eq!(attr("deposit_amount"), Some("100"));
+        assert_eq!(attr("ask_denom"), Some("uosmo"));
+        assert_eq!(attr("ask_amount"), Some("200"));
+
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv(
+                "channel-0",
+                &envelope(PacketMsg::AcceptSideA {
+                    id: 0,
+                    sender: "taker".to_string(),
+                    price: None,
+                    recipient: None,
+                }),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let event = &res.events[0];
+        assert_eq!(event.ty, "swap_settled");
+        assert!(event
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "taker" && attr.value == "taker"));
+    }
+
+    fn bech32_addr(prefix: &str, seed: u8) -> String {
+        bech32::encode(prefix, [seed; 20].to_base32(), Variant::Bech32).unwrap()
+    }
+
+    #[test]
+    fn test_payout_recipients() {
+        let maker = bech32_addr("juno", 1);
+        let cold_wallet = bech32_addr("juno", 2);
+        let taker = bech32_addr("osmo", 3);
+        let vault = bech32_addr("osmo", 4);
+
+        // Side A: the maker's recipient must live on the maker's chain.
+        let mut deps = setup();
+        let create = |recipient: &str| ExecuteMsg::CreateSwap {
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask_transfer_channel_id: "channel-2".to_string(),
+            htlc: None,
+            dutch_auction: None,
+            english_auction: None,
+            recipient: Some(recipient.to_string()),
+            refund_address: None,
+            allowed_takers: None,
+            local: false,
+        };
+        for recipient in [vault.as_str(), "not-an-address"] {
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(&maker, &coins(100, "ujuno")),
+                create(recipient),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidRecipient { .. }));
+        }
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(&maker, &coins(100, "ujuno")),
+            create(&cold_wallet),
+        )
+        .unwrap();
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.recipient, Some(Addr::unchecked(&cold_wallet)));
+
+        // The deposit is released to the recipient named by the taker.
+        let accept = envelope(PacketMsg::AcceptSideA {
+            id: 0,
+            sender: taker.clone(),
+            price: None,
+            recipient: Some(vault.clone()),
+        });
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-1".to_string(),
+                to_address: vault.clone(),
+                amount: coin(100, "ujuno"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // Side B: the taker's recipient must live on the taker's chain.
+        let mut deps = setup();
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 0, swap }),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(&taker, &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: Some(cold_wallet.clone()),
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRecipient { .. }));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(&taker, &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: Some(vault.clone()),
+                local: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &accept.msg).unwrap(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // The ask is released to the maker's recipient on the ack.
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accept,
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-2".to_string(),
+                to_address: cold_wallet,
+                amount: coin(200, "uosmo"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_create_from_ibc_hooks() {
+        // ibc-hooks executes memos as a derived sender no key controls,
+        // with the funds in their `ibc/` voucher denom.
+        let derived = bech32::encode("juno", [7u8; 32].to_base32(), Variant::Bech32).unwrap();
+        let voucher = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+        let recipient = bech32_addr("cosmos", 1);
+        let mut deps = setup();
+        let create = |recipient: Option<String>| ExecuteMsg::CreateSwap {
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask_transfer_channel_id: "channel-2".to_string(),
+            htlc: None,
+            dutch_auction: None,
+            english_auction: None,
+            recipient,
+            refund_address: Some("fallback".to_string()),
+            allowed_takers: None,
+            local: false,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(&derived, &coins(100, voucher)),
+            create(None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RecipientRequired {}));
+
+        // The recipient may live on the chain the ask is sent to.
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(&derived, &coins(100, voucher)),
+            create(Some(recipient.clone())),
+        )
+        .unwrap();
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.deposit, token(voucher, 100));
+        assert_eq!(swap.maker_payee(), &Addr::unchecked(&recipient));
+
+        // Refunds go to the fallback address instead of the sender.
+        let listing = envelope(PacketMsg::CreateSideB { id: 0, swap });
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &listing,
+                IbcAcknowledgement::new(StdAck::fail("refused".to_string())),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "fallback".to_string(),
+                amount: coins(100, voucher),
+            }
+            .into()
+        );
+
+        // No key controls the maker, so the fallback may cancel.
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(&derived, &coins(100, voucher)),
+            create(Some(recipient)),
+        )
+        .unwrap();
+        let swap = swaps_a().load(&deps.storage, 1).unwrap();
+        ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 1, swap }),
+                IbcAcknowledgement::new(StdAck::success(1)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let cancel = |deps: DepsMut, sender: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::CancelSwap { id: 1 },
+            )
+        };
+        let err = cancel(deps.as_mut(), "stranger").unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = cancel(deps.as_mut(), "fallback").unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &PacketMsg::CancelSideB { id: 1 }).unwrap(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_accept_swap_with_route() {
+        let mut deps = setup();
+        receive_swap(deps.as_mut(), 0, "maker", "ujuno", "uosmo");
+        let route = vec![SwapAmountInRoute {
+            pool_id: 1,
+            token_out_denom: "uosmo".to_string(),
+        }];
+        let accept = |route: Vec<SwapAmountInRoute>| ExecuteMsg::AcceptSwapWithRoute {
+            id: 0,
+            route,
+            max_input: Uint128::new(400),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(500, "uatom")),
+            accept(route.clone()),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NoSwapRouter {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetSwapRouter {
+                address: "router".to_string(),
+            },
+        )
+        .unwrap();
+        let wrong_route = vec![SwapAmountInRoute {
+            pool_id: 2,
+            token_out_denom: "ujuno".to_string(),
+        }];
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(500, "uatom")),
+            accept(wrong_route),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoute {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &[]),
+            accept(route.clone()),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::NoFunds {})
+        ));
+
+        // The router is asked for at least the ask, the input it is not
+        // given is returned.
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(500, "uatom")),
+            accept(route.clone()),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::reply_on_success(
+                WasmMsg::Execute {
+                    contract_addr: "router".to_string(),
+                    msg: to_binary(&SwapRouterMsg::Swap {
+                        input_coin: coin(400, "uatom"),
+                        output_denom: "uosmo".to_string(),
+                        minimum_output_amount: Uint128::new(200),
+                        route,
+                    })
+                    .unwrap(),
+                    funds: coins(400, "uatom"),
+                },
+                ROUTE_REPLY_ID,
+            )
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(100, "uatom"),
+            }
+            .into()
+        );
+
+        // The mock router pays in more than the ask, the surplus goes
+        // back to the taker once the swap is accepted.
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(230, "uosmo"));
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: ROUTE_REPLY_ID,
+                result: SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: None,
+                }),
+            },
+        )
+        .unwrap();
+        let accept_msg = PacketMsg::AcceptSideA {
+            id: 0,
+            sender: "taker".to_string(),
+            price: None,
+            recipient: None,
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &accept_msg).unwrap(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(30, "uosmo"),
+            }
+            .into()
+        );
+        let swap = swaps_b().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.status, SwapStatus::Accepted);
+        assert_eq!(swap.taker, Some(Addr::unchecked("taker")));
+        assert!(ROUTED_ACCEPT.may_load(&deps.storage).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_local_swap() {
+        let mut deps = setup();
+        let create = |htlc: Option<HtlcMsg>| ExecuteMsg::CreateSwap {
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: String::new(),
+            ask_transfer_channel_id: String::new(),
+            htlc,
+            dutch_auction: None,
+            english_auction: None,
+            recipient: None,
+            refund_address: None,
+            allowed_takers: None,
+            local: true,
+        };
+        let hashlock = HexBinary::from(Sha256::digest(b"secret").to_vec());
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            create(Some(HtlcMsg {
+                hashlock,
+                timelock: 3600,
+            })),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IncompatibleModes {}));
+
+        // Listed without a packet, open to takers right away.
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            create(None),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "channel" && attr.value == LOCAL_CHANNEL));
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.status, SwapStatus::Open);
+
+        // Side B can not settle or withdraw it.
+        for msg in [
+            PacketMsg::AcceptSideA {
+                id: 0,
+                sender: "taker".to_string(),
+                price: None,
+                recipient: None,
+            },
+            PacketMsg::RefundSideA { id: 0 },
+        ] {
+            let res = ibc_packet_receive(
+                deps.as_mut(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &envelope(msg)).unwrap(),
+            )
+            .unwrap();
+            assert!(res.messages.is_empty());
+            assert_eq!(
+                res.acknowledgement,
+                StdAck::fail(ContractError::LocalSwap {}.to_string())
+            );
+        }
+
+        let accept = |local: bool| ExecuteMsg::AcceptSwap {
+            id: 0,
+            recipient: None,
+            local,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            accept(false),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(199, "uosmo")),
+            accept(true),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &[coin(200, "uosmo"), coin(1, "ujuno")]),
+            accept(true),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::MultipleDenoms {})
+        ));
+
+        // Both legs and the overpayment are settled by bank sends.
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(250, "uosmo")),
+            accept(true),
+        )
+        .unwrap();
+        let sends: Vec<CosmosMsg> = vec![
+            BankMsg::Send {
+                to_address: "maker".to_string(),
+                amount: coins(200, "uosmo"),
+            }
+            .into(),
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(100, "ujuno"),
+            }
+            .into(),
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(50, "uosmo"),
+            }
+            .into(),
+        ];
+        assert_eq!(
+            res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>(),
+            sends
+        );
+        assert_eq!(res.events[0].ty, "swap_settled");
+        assert!(swaps_a().may_load(&deps.storage, 0).unwrap().is_none());
+        assert_eq!(stats(deps.as_ref()).stats.swaps_settled, 1);
+
+        // Cross-chain swaps are not settled locally.
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 1,
+                recipient: None,
+                local: true,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NotLocal {}));
+    }
+
+    #[test]
+    fn test_batch_swaps() {
+        // Side A: the deposits must add up to the funds sent.
+        let mut deps = setup();
+        let offer = |deposit: u128| SwapOffer {
+            deposit: token("ujuno", deposit),
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask_transfer_channel_id: "channel-2".to_string(),
+            htlc: None,
+            dutch_auction: None,
+            english_auction: None,
+            recipient: None,
+            refund_address: None,
+            allowed_takers: None,
+            local: false,
+        };
+        let batch = ExecuteMsg::BatchCreateSwaps {
+            swaps: vec![offer(60), offer(50)],
+        };
+        for funds in [
+            coins(100, "ujuno"),
+            coins(120, "ujuno"),
+            coins(110, "uatom"),
+        ] {
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("maker", &funds),
+                batch.clone(),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidBatchFunds {}));
+        }
+        // Single swaps still escrow exactly one coin.
+        let single = ExecuteMsg::CreateSwap {
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask_transfer_channel_id: "channel-2".to_string(),
+            htlc: None,
+            dutch_auction: None,
+            english_auction: None,
+            recipient: None,
+            refund_address: None,
+            allowed_takers: None,
+            local: false,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &[]),
+            single.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::NoFunds {})
+        ));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &[coin(60, "ujuno"), coin(50, "uatom")]),
+            single,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::MultipleDenoms {})
+        ));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(110, "ujuno")),
+            batch,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.events.len(), 2);
+
+        // Each listing is rolled back on its own.
+        let listing = envelope(PacketMsg::CreateSideB {
+            id: 1,
+            swap: swaps_a().load(&deps.storage, 1).unwrap(),
+        });
+        let res = ibc_packet_timeout(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_timeout("channel-0", &listing).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "maker".to_string(),
+                amount: coins(50, "ujuno"),
+            }
+            .into()
+        );
+        assert!(swaps_a().has(&deps.storage, 0));
+        assert!(!swaps_a().has(&deps.storage, 1));
+
+        // Side B: the asks are paid out of the funds sent, with change.
+        let mut deps = setup();
+        for id in 0..3 {
+            receive_swap(deps.as_mut(), id, "maker", "ujuno", "uosmo");
+        }
+        let batch = ExecuteMsg::BatchAcceptSwaps {
+            ids: vec![0, 1],
+            recipient: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(399, "uosmo")),
+            batch.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(450, "uosmo")),
+            batch,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(
+            res.messages[2].msg,
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(50, "uosmo"),
+            }
+            .into()
+        );
+        assert_eq!(
+            swaps_b().load(&deps.storage, 2).unwrap().status,
+            SwapStatus::Open
+        );
+
+        // A timed out or refused accept refunds the taker and reopens
+        // that swap only.
+        let accept = |id: u64| {
+            envelope(PacketMsg::AcceptSideA {
+                id,
+                sender: "taker".to_string(),
+                price: None,
+                recipient: None,
+            })
+        };
+        let refund: CosmosMsg = BankMsg::Send {
+            to_address: "taker".to_string(),
+            amount: coins(200, "uosmo"),
+        }
+        .into();
+        let res = ibc_packet_timeout(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_timeout("channel-0", &accept(0)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].msg, refund);
+        let swap = swaps_b().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.status, SwapStatus::Open);
+        assert_eq!(swap.taker, None);
+        assert_eq!(
+            swaps_b().load(&deps.storage, 1).unwrap().status,
+            SwapStatus::Accepted
+        );
+
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accept(1),
+                IbcAcknowledgement::new(StdAck::fail("refused".to_string())),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].msg, refund);
+        assert_eq!(
+            swaps_b().load(&deps.storage, 1).unwrap().status,
+            SwapStatus::Open
+        );
+    }
+
+    #[test]
+    fn test_denom_rules() {
+        let mut deps = setup();
+        let rule = |denom: &str, rule: Option<DenomRule>| ExecuteMsg::SetDenomRule {
+            denom: denom.to_string(),
+            rule,
+        };
+        let limits = DenomRule {
+            denied: false,
+            min_amount: Some(Uint128::new(50)),
+            max_amount: Some(Uint128::new(500)),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &[]),
+            rule("ujuno", Some(limits.clone())),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            rule("ujuno", Some(limits.clone())),
+        )
+        .unwrap();
+        let denied = DenomRule {
+            denied: true,
+            ..Default::default()
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            rule("uscam", Some(denied.clone())),
+        )
+        .unwrap();
+
+        let create = |deps: DepsMut, denom: &str, amount: u128| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker", &coins(amount, denom)),
+                ExecuteMsg::CreateSwap {
+                    ask: token("uosmo", 200),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    recipient: None,
+                    refund_address: None,
+                    allowed_takers: None,
+                    local: false,
+                },
+            )
+        };
+        for amount in [49, 501] {
+            let err = create(deps.as_mut(), "ujuno", amount).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidSwapSize { .. }));
+        }
+        let err = create(deps.as_mut(), "uscam", 100).unwrap_err();
+        assert!(matches!(err, ContractError::DenomNotAllowed { .. }));
+        create(deps.as_mut(), "ujuno", 100).unwrap();
+        create(deps.as_mut(), "uatom", 100).unwrap();
+
+        // With the allowlist on, denoms without a rule are refused too.
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetDenomAllowlist { enabled: true },
+        )
+        .unwrap();
+        let err = create(deps.as_mut(), "uatom", 100).unwrap_err();
+        assert!(matches!(err, ContractError::DenomNotAllowed { .. }));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            rule("uosmo", Some(DenomRule::default())),
+        )
+        .unwrap();
+        create(deps.as_mut(), "ujuno", 100).unwrap();
+
+        let res: DenomRulesResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::DenomRules {
+                    start_after: Some("ujuno".to_string()),
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(res.allowlist);
+        assert_eq!(
+            res.rules,
+            vec![
+                DenomRuleItem {
+                    denom: "uosmo".to_string(),
+                    rule: DenomRule::default(),
+                },
+                DenomRuleItem {
+                    denom: "uscam".to_string(),
+                    rule: denied.clone(),
+                },
+            ]
+        );
+
+        // Side B refuses denied listings with an error ack, and a denom
+        // denied after listing can no longer be accepted.
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            rule("uscam", Some(denied)),
+        )
+        .unwrap();
+        receive_swap(deps.as_mut(), 0, "maker", "uscam", "uosmo");
+        assert!(!swaps_b().has(&deps.storage, 0));
+
+        receive_swap(deps.as_mut(), 1, "maker", "ujuno", "uosmo");
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            rule(
+                "uosmo",
+                Some(DenomRule {
+                    max_amount: Some(Uint128::new(150)),
+                    ..limits
+                }),
+            ),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 1,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwapSize { .. }));
+    }
+
+    fn set_rate_limit(deps: DepsMut, channel_id: &str, denom: &str, max_amount: u128) {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetRateLimit {
+                channel_id: channel_id.to_string(),
+                denom: denom.to_string(),
+                limit: Some(RateLimit {
+                    window: 100,
+                    max_amount: Uint128::new(max_amount),
+                }),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_price_oracle() {
+        let mut deps = setup();
+        // The mock oracle prices ujuno at 2 uosmo and knows no other pair.
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "oracle" => {
+                match from_binary(msg).unwrap() {
+                    OracleQueryMsg::Price {
+                        base_denom,
+                        quote_denom,
+                    } if base_denom == "ujuno" && quote_denom == "uosmo" => {
+                        let price = PriceResponse {
+                            price: Decimal::percent(200),
+                        };
+                        SystemResult::Ok(ContractResult::Ok(to_binary(&price).unwrap()))
+                    }
+                    _ => SystemResult::Ok(ContractResult::Err("unknown pair".to_string())),
+                }
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+        let set_oracle = |deps: DepsMut, sender: &str, enforce: bool| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SetPriceOracle {
+                    oracle: Some(PriceOracleMsg {
+                        address: "oracle".to_string(),
+                        tolerance: Decimal::percent(50),
+                        enforce,
+                    }),
+                },
+            )
+        };
+        let err = set_oracle(deps.as_mut(), "maker", true).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        set_oracle(deps.as_mut(), "owner", true).unwrap();
+        let oracle: Option<PriceOracle> =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PriceOracle {}).unwrap())
+                .unwrap();
+        assert_eq!(oracle.unwrap().address, Addr::unchecked("oracle"));
+
+        let create = |deps: DepsMut, denom: &str, amount: u128| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker", &coins(amount, denom)),
+                ExecuteMsg::CreateSwap {
+                    ask: token("uosmo", 200),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    recipient: None,
+                    refund_address: None,
+                    allowed_takers: None,
+                    local: false,
+                },
+            )
+        };
+        // An ask of 200 uosmo prices ujuno deposits of 67 to 400 within
+        // 50% of the oracle.
+        for amount in [10, 401] {
+            let err = create(deps.as_mut(), "ujuno", amount).unwrap_err();
+            assert!(matches!(err, ContractError::PriceDeviation { .. }));
+        }
+        let res = create(deps.as_mut(), "ujuno", 70).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .all(|attr| attr.key != "oracle_price"));
+        // Pairs the oracle can not price are refused, as is any swap
+        // while the oracle is down.
+        let err = create(deps.as_mut(), "uatom", 1).unwrap_err();
+        assert!(matches!(err, ContractError::NoOraclePrice { .. }));
+
+        // Dutch auctions are checked at the price they are accepted at.
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: Some(DutchAuctionMsg {
+                    floor: Uint128::new(20),
+                    duration: 100,
+                }),
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: true,
+            },
+        )
+        .unwrap();
+        let accept = |deps: DepsMut, seconds: u64| {
+            execute(
+                deps,
+                env_at(seconds),
+                mock_info("taker", &coins(200, "uosmo")),
+                ExecuteMsg::AcceptSwap {
+                    id: 1,
+                    recipient: None,
+                    local: true,
+                },
+            )
+        };
+        // 38 uosmo for 100 ujuno is far below the oracle's price.
+        let err = accept(deps.as_mut(), 90).unwrap_err();
+        assert!(matches!(err, ContractError::PriceDeviation { .. }));
+        accept(deps.as_mut(), 10).unwrap();
+        deps.querier.update_wasm(|_| {
+            SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            })
+        });
+        let err = create(deps.as_mut(), "ujuno", 70).unwrap_err();
+        assert!(matches!(err, ContractError::NoOraclePrice { .. }));
+    }
+
+    #[test]
+    fn test_price_oracle_flags() {
+        let mut deps = setup();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "oracle" => {
+                let price = PriceResponse {
+                    price: Decimal::percent(200),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_binary(&price).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+        let set_oracle = |deps: DepsMut, sender: &str, enforce: bool| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SetPriceOracle {
+                    oracle: Some(PriceOracleMsg {
+                        address: "oracle".to_string(),
+                        tolerance: Decimal::percent(50),
+                        enforce,
+                    }),
+                },
+            )
+        };
+        let create = |deps: DepsMut, denom: &str, amount: u128| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker", &coins(amount, denom)),
+                ExecuteMsg::CreateSwap {
+                    ask: token("uosmo", 200),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    recipient: None,
+                    refund_address: None,
+                    allowed_takers: None,
+                    local: false,
+                },
+            )
+        };
+
+        // Without enforcement, deviating swaps are created and flagged.
+        set_oracle(deps.as_mut(), "owner", false).unwrap();
+        let res = create(deps.as_mut(), "ujuno", 10).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "oracle_price" && attr.value == "2"));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetPriceOracle { oracle: None },
+        )
+        .unwrap();
+        let res = create(deps.as_mut(), "ujuno", 10).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .all(|attr| attr.key != "oracle_price"));
+    }
+
+    #[test]
+    fn test_tokenized_positions() {
+        let mut deps = setup();
+        // The mock cw721 reports the position of swap 0 sold to "buyer".
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "nft" => {
+                match from_binary(msg).unwrap() {
+                    Cw721QueryMsg::OwnerOf { token_id, .. } if token_id == "0" => {
+                        let owner = OwnerOfResponse {
+                            owner: "buyer".to_string(),
+                            approvals: vec![],
+                        };
+                        SystemResult::Ok(ContractResult::Ok(to_binary(&owner).unwrap()))
+                    }
+                    Cw721QueryMsg::OwnerOf { token_id, .. } if token_id == "2" => {
+                        SystemResult::Ok(ContractResult::Err("contract is paused".to_string()))
+                    }
+                    _ => SystemResult::Ok(ContractResult::Err("token not found".to_string())),
+                }
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+        let tokenize = |deps: DepsMut, sender: &str, id: u64| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::TokenizeSwap { id },
+            )
+        };
+        let cancel = |deps: DepsMut, sender: &str, id: u64| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::CancelSwap { id },
+            )
+        };
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        let err = tokenize(deps.as_mut(), "maker", 0).unwrap_err();
+        assert!(matches!(err, ContractError::NoPositionNft {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetPositionNft {
+                address: "nft".to_string(),
+            },
+        )
+        .unwrap();
+        let err = tokenize(deps.as_mut(), "buyer", 0).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = tokenize(deps.as_mut(), "maker", 0).unwrap();
+        let mint = Cw721BaseExecuteMsg::<Empty, Empty>::Mint(MintMsg {
+            token_id: "0".to_string(),
+            owner: "maker".to_string(),
+            token_uri: None,
+            extension: Empty {},
+        });
+        assert_eq!(
+            res.messages[0].msg,
+            WasmMsg::Execute {
+                contract_addr: "nft".to_string(),
+                msg: to_binary(&mint).unwrap(),
+                funds: vec![],
+            }
+            .into()
+        );
+        let err = tokenize(deps.as_mut(), "maker", 0).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyTokenized {}));
+
+        // Only the holder may cancel, once side B listed the swap.
+        let err = cancel(deps.as_mut(), "buyer", 0).unwrap_err();
+        assert!(matches!(err, ContractError::SwapNotOpen {}));
+        for id in [0, 1] {
+            let swap = swaps_a().load(&deps.storage, id).unwrap();
+            ibc_packet_ack(
+                deps.as_mut(),
+                mock_env(),
+                mock_ibc_packet_ack(
+                    "channel-0",
+                    &envelope(PacketMsg::CreateSideB { id, swap }),
+                    IbcAcknowledgement::new(StdAck::success(id)),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        }
+        let err = cancel(deps.as_mut(), "maker", 0).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = cancel(deps.as_mut(), "buyer", 0).unwrap();
+        let cancel_packet = envelope(PacketMsg::CancelSideB { id: 0 });
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &cancel_packet.msg).unwrap(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // Side B accepted the swap first, so the holder is paid the ask.
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &cancel_packet,
+                IbcAcknowledgement::new(StdAck::fail("Swap is not open".to_string())),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let accept = envelope(PacketMsg::AcceptSideA {
+            id: 0,
+            sender: "taker".to_string(),
+            price: None,
+            recipient: None,
+        });
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept).unwrap(),
+        )
+        .unwrap();
+        let payee_ack = StdAck::success(AcceptAck {
+            id: 0,
+            payee: "buyer".to_string(),
+        });
+        assert_eq!(res.acknowledgement, payee_ack);
+        assert!(!POSITIONS.has(&deps.storage, 0));
+
+        // Untokenized swaps are cancelled by the maker, and refunded
+        // once side B withdrew the listing.
+        cancel(deps.as_mut(), "maker", 1).unwrap();
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::CancelSideB { id: 1 }),
+                IbcAcknowledgement::new(StdAck::success(1)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "maker".to_string(),
+                amount: coins(100, "ujuno"),
+            }
+            .into()
+        );
+        assert!(swaps_a().may_load(&deps.storage, 1).unwrap().is_none());
+
+        // Only untokenized swaps fall back to the maker. A position whose
+        // owner can not be queried, failing or burned, lets nobody act
+        // in the holder's place.
+        assert_eq!(execute::position_holder(deps.as_ref(), 1).unwrap(), None);
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        for id in [2, 3] {
+            tokenize(deps.as_mut(), "maker", id).unwrap();
+        }
+        for id in [2, 3] {
+            assert!(execute::position_holder(deps.as_ref(), id).is_err());
+            let err = cancel(deps.as_mut(), "maker", id).unwrap_err();
+            assert!(matches!(
+                err,
+                ContractError::Std(StdError::GenericErr { .. })
+            ));
+        }
+
+        // Side B pays the ask to the holder named in the ack.
+        let mut deps = setup();
+        for id in [0, 1] {
+            receive_swap(deps.as_mut(), id, "maker", "ujuno", "uosmo");
+        }
+        accept_swap(deps.as_mut(), "taker", 0);
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack("channel-0", &accept, IbcAcknowledgement::new(payee_ack)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-2".to_string(),
+                to_address: "buyer".to_string(),
+                amount: coin(200, "uosmo"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // Open listings are withdrawn, accepted ones are kept.
+        accept_swap(deps.as_mut(), "taker", 1);
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &envelope(PacketMsg::CancelSideB { id: 1 })).unwrap(),
+        )
+        .unwrap();
+        let ack: StdAck = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(ack.unwrap_err(), ContractError::SwapNotOpen {}.to_string());
+        receive_swap(deps.as_mut(), 2, "maker", "ujuno", "uosmo");
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &envelope(PacketMsg::CancelSideB { id: 2 })).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.acknowledgement, StdAck::success(2));
+        assert!(swaps_b().may_load(&deps.storage, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_nft_swaps() {
+        // "collection" and "fakes" are cw721 contracts, only the first
+        // of which the owner allows.
+        let allow_collection = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            deps.querier.update_wasm(|query| match query {
+                WasmQuery::ContractInfo { contract_addr }
+                    if contract_addr == "collection" || contract_addr == "fakes" =>
+                {
+                    let info = cosmwasm_std::ContractInfoResponse::new(1, "creator");
+                    SystemResult::Ok(ContractResult::Ok(to_binary(&info).unwrap()))
+                }
+                _ => SystemResult::Err(SystemError::NoSuchContract {
+                    addr: "unknown".to_string(),
+                }),
+            });
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("owner", &[]),
+                ExecuteMsg::AllowNftCollection {
+                    address: "collection".to_string(),
+                },
+            )
+            .unwrap();
+        };
+        let mut deps = setup();
+        let receive_nft = |deps: DepsMut, collection: &str, sender: &str, token_id: &str, msg| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(collection, &[]),
+                ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                    sender: sender.to_string(),
+                    token_id: token_id.to_string(),
+                    msg: to_binary(&msg).unwrap(),
+                }),
+            )
+        };
+        let set_bridge = |deps: DepsMut| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("owner", &[]),
+                ExecuteMsg::SetNftBridge {
+                    address: "bridge".to_string(),
+                },
+            )
+            .unwrap()
+        };
+        let send_nft = |token_id: &str, receiver: &str, channel_id: &str| -> CosmosMsg {
+            let outgoing = Ics721OutgoingMsg {
+                receiver: receiver.to_string(),
+                channel_id: channel_id.to_string(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            };
+            WasmMsg::Execute {
+                contract_addr: "collection".to_string(),
+                msg: to_binary(&Cw721ExecuteMsg::SendNft {
+                    contract: "bridge".to_string(),
+                    token_id: token_id.to_string(),
+                    msg: to_binary(&outgoing).unwrap(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }
+            .into()
+        };
+        let create = ReceiveNftMsg::CreateSwap {
+            ask: token("uosmo", 200),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask_transfer_channel_id: "channel-2".to_string(),
+            recipient: None,
+            allowed_takers: None,
+        };
+
+        // Only allowed collection contracts can deliver NFTs.
+        let err = receive_nft(
+            deps.as_mut(),
+            "collection",
+            "maker",
+            "punk-1",
+            create.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        allow_collection(&mut deps);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("stranger", &[]),
+            ExecuteMsg::AllowNftCollection {
+                address: "fakes".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err =
+            receive_nft(deps.as_mut(), "fakes", "maker", "punk-1", create.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NftCollectionNotAllowed { .. }));
+        let err =
+            receive_nft(deps.as_mut(), "forger", "maker", "punk-1", create.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let collections: NftCollectionsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::NftCollections {}).unwrap())
+                .unwrap();
+        assert_eq!(collections.collections, vec![Addr::unchecked("collection")]);
+
+        // The NFT deposit can only be sent on through a bridge.
+        let err = receive_nft(
+            deps.as_mut(),
+            "collection",
+            "maker",
+            "punk-1",
+            create.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NoNftBridge {}));
+        set_bridge(deps.as_mut());
+        receive_nft(deps.as_mut(), "collection", "maker", "punk-1", create).unwrap();
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.deposit, Token::nft("collection", "punk-1".to_string()));
+        assert_eq!(swap.deposit_address, Addr::unchecked("maker"));
+
+        // NFT asks are single tokens.
+        let mut ask = Token::nft("collection", "punk-2".to_string());
+        ask.amount = Uint128::new(2);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask,
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidNft {}));
+        // Nor can a native denom carry a token id.
+        let mut ask = token("uosmo", 1);
+        ask.token_id = Some("punk-2".to_string());
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: ask.clone(),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidNft {}));
+        assert!(ask.to_coin().is_err());
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &[]),
+            ExecuteMsg::RequestQuote {
+                want: Token::nft("collection", "punk-2".to_string()),
+                pay_denom: Denom::Native("uosmo".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidNft {}));
+
+        // Side A settles by sending the NFT to the taker over the bridge.
+        ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 0, swap }),
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let accept = envelope(PacketMsg::AcceptSideA {
+            id: 0,
+            sender: "taker".to_string(),
+            price: None,
+            recipient: None,
+        });
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            send_nft("punk-1", "taker", "channel-1")
+        );
+
+        // Side B accepts a swap asking for an NFT with that very token.
+        let mut deps = setup();
+        allow_collection(&mut deps);
+        receive_swap(deps.as_mut(), 0, "maker", "ujuno", "uosmo");
+        let mut swap = swaps_b().load(&deps.storage, 0).unwrap();
+        swap.ask = Token::nft("collection", "punk-1".to_string());
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 1, swap }),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let accept_nft = ReceiveNftMsg::AcceptSwap {
+            id: 1,
+            recipient: None,
+        };
+        let err = receive_nft(
+            deps.as_mut(),
+            "collection",
+            "taker",
+            "punk-1",
+            accept_nft.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NoNftBridge {}));
+        set_bridge(deps.as_mut());
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 1,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidNft {}));
+        let err = receive_nft(
+            deps.as_mut(),
+            "collection",
+            "taker",
+            "punk-2",
+            accept_nft.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidNft {}));
+        let err = receive_nft(
+            deps.as_mut(),
+            "fakes",
+            "taker",
+            "punk-1",
+            accept_nft.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NftCollectionNotAllowed { .. }));
+        let err = receive_nft(
+            deps.as_mut(),
+            "collection",
+            "taker",
+            "punk-1",
+            ReceiveNftMsg::AcceptSwap {
+                id: 0,
+                recipient: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidNft {}));
+        // Native asks are still paid in their own denom.
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("taker", &coins(200, "ujuno")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::MissingDenom(_))
+        ));
+        receive_nft(deps.as_mut(), "collection", "taker", "punk-1", accept_nft).unwrap();
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &envelope(PacketMsg::AcceptSideA {
+                    id: 1,
+                    sender: "taker".to_string(),
+                    price: None,
+                    recipient: None,
+                }),
+                IbcAcknowledgement::new(StdAck::success(1)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            send_nft("punk-1", "maker", "channel-2")
+        );
+    }
+
+    #[test]
+    fn test_taker_allowlists() {
+        let mut deps = setup();
+        // The cw4 group "desk" has "bob" as its only member.
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Raw { contract_addr, key } if contract_addr == "desk" => {
+                let weight = key.ends_with(b"bob").then_some(1u64);
+                let value = weight.map(|w| to_binary(&w).unwrap()).unwrap_or_default();
+                SystemResult::Ok(ContractResult::Ok(value))
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+        let create = |deps: DepsMut, allowed_takers: TakerAllowlist, local: bool| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker", &coins(100, "ujuno")),
+                ExecuteMsg::CreateSwap {
+                    ask: token("uosmo", 200),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: None,
+                    recipient: None,
+                    refund_address: None,
+                    allowed_takers: Some(allowed_takers),
+                    local,
+                },
+            )
+        };
+        let accept = |deps: DepsMut, taker: &str, id: u64, local: bool| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(taker, &coins(200, "uosmo")),
+                ExecuteMsg::AcceptSwap {
+                    id,
+                    recipient: None,
+                    local,
+                },
+            )
+        };
+        let addresses = TakerAllowlist::Addresses(vec!["alice".to_string()]);
+        let group = TakerAllowlist::Cw4Group("desk".to_string());
+
+        let err = create(deps.as_mut(), TakerAllowlist::Addresses(vec![]), false).unwrap_err();
+        assert!(matches!(err, ContractError::EmptyAllowlist {}));
+        create(deps.as_mut(), addresses.clone(), false).unwrap();
+        create(deps.as_mut(), group.clone(), false).unwrap();
+        create(deps.as_mut(), addresses.clone(), true).unwrap();
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.allowed_takers, Some(addresses));
+
+        // Local swaps are restricted on this chain.
+        let err = accept(deps.as_mut(), "bob", 2, true).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        accept(deps.as_mut(), "alice", 2, true).unwrap();
+
+        // Side B enforces the allowlist the listing carries.
+        for id in [0, 1] {
+            let swap = swaps_a().load(&deps.storage, id).unwrap();
+            ibc_packet_receive(
+                deps.as_mut(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &envelope(PacketMsg::CreateSideB { id, swap }))
+                    .unwrap(),
+            )
+            .unwrap();
+        }
+        let err = accept(deps.as_mut(), "bob", 0, false).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        accept(deps.as_mut(), "alice", 0, false).unwrap();
+        let err = accept(deps.as_mut(), "alice", 1, false).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        accept(deps.as_mut(), "bob", 1, false).unwrap();
+        let swap = swaps_b().load(&deps.storage, 1).unwrap();
+        assert_eq!(swap.taker, Some(Addr::unchecked("bob")));
+    }
+
+    /// Moves the funds sent in and the coins `msgs` pay out through the
+    /// contract's mock balance, then checks the ledger matches it.
+    fn assert_solvent(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        funds: &[Coin],
+        msgs: &[SubMsg],
+    ) {
+        let querier = deps.as_ref().querier;
+        let mut balance = NativeBalance(querier.query_all_balances(MOCK_CONTRACT_ADDR).unwrap());
+        for coin in funds {
+            balance += coin.clone();
+        }
+        for msg in msgs {
+            let paid = match &msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+                CosmosMsg::Ibc(IbcMsg::Transfer { amount, .. }) => vec![amount.clone()],
+                CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => funds.clone(),
+                _ => vec![],
+            };
+            balance = (balance - paid).unwrap();
+        }
+        balance.normalize();
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, balance.into_vec());
+
+        let res: SolvencyResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Solvency {}).unwrap()).unwrap();
+        assert!(res.solvent);
+        for denom in res.denoms {
+            assert_eq!(denom.owed, denom.balance, "{}", denom.denom);
+        }
+    }
+
+    #[test]
+    fn test_escrow_solvency() {
+        let mut deps = setup();
+        let list = |mut deps: DepsMut, id: u64, ok: bool| {
+            let swap = swaps_a().load(deps.storage, id).unwrap();
+            let packet = envelope(PacketMsg::CreateSideB { id, swap });
+            ibc_packet_receive(
+                deps.branch(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &packet).unwrap(),
+            )
+            .unwrap();
+            let ack = match ok {
+                true => StdAck::success(id),
+                false => StdAck::fail("refused".to_string()),
+            };
+            ibc_packet_ack(
+                deps,
+                mock_env(),
+                mock_ibc_packet_ack("channel-0", &packet, IbcAcknowledgement::new(ack)).unwrap(),
+            )
+            .unwrap()
+        };
+        let accept = |deps: DepsMut, id: u64, local: bool| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("taker", &coins(250, "uosmo")),
+                ExecuteMsg::AcceptSwap {
+                    id,
+                    recipient: None,
+                    local,
+                },
+            )
+            .unwrap()
+        };
+        let accepted = |id: u64| {
+            envelope(PacketMsg::AcceptSideA {
+                id,
+                sender: "taker".to_string(),
+                price: None,
+                recipient: None,
+            })
+        };
+
+        // Deposits are owed to their makers until side B lists them.
+        for _ in 0..3 {
+            create_swap(deps.as_mut(), "maker", "ujuno", 100);
+            assert_solvent(&mut deps, &coins(100, "ujuno"), &[]);
+        }
+        let res = list(deps.as_mut(), 0, true);
+        assert_solvent(&mut deps, &[], &res.messages);
+        let res = list(deps.as_mut(), 1, false);
+        assert_solvent(&mut deps, &[], &res.messages);
+        list(deps.as_mut(), 2, true);
+
+        // Payments are owed to takers until side A settles, and what
+        // they overpaid is returned right away.
+        let res = accept(deps.as_mut(), 0, false);
+        assert_solvent(&mut deps, &coins(250, "uosmo"), &res.messages);
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accepted(0)).unwrap(),
+        )
+        .unwrap();
+        assert_solvent(&mut deps, &[], &res.messages);
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accepted(0),
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_solvent(&mut deps, &[], &res.messages);
+
+        // Side A refused the accept, so the taker is paid back.
+        let res = accept(deps.as_mut(), 2, false);
+        assert_solvent(&mut deps, &coins(250, "uosmo"), &res.messages);
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accepted(2),
+                IbcAcknowledgement::new(StdAck::fail("refused".to_string())),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_solvent(&mut deps, &[], &res.messages);
+
+        // Local swaps settle in one go.
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: true,
+            },
+        )
+        .unwrap();
+        assert_solvent(&mut deps, &coins(100, "ujuno"), &[]);
+        let res = accept(deps.as_mut(), 3, true);
+        assert_solvent(&mut deps, &coins(250, "uosmo"), &res.messages);
+
+        // Only the deposit of swap 2 is still owed.
+        let res: SolvencyResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Solvency {}).unwrap()).unwrap();
+        assert_eq!(
+            res.denoms,
+            vec![DenomSolvency {
+                denom: "ujuno".to_string(),
+                owed: Uint128::new(100),
+                balance: Uint128::new(100),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_channel_authorization() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            InstantiateMsg {
+                packet_lifetime: 60,
+            },
+        )
+        .unwrap();
+        let authorize = |deps: DepsMut, sender: &str, authorized: bool| {
+            let connection_id = "connection-2".to_string();
+            let counterparty_port_id = "their-port".to_string();
+            let msg = match authorized {
+                true => ExecuteMsg::AuthorizeChannel {
+                    connection_id,
+                    counterparty_port_id,
+                },
+                false => ExecuteMsg::RevokeChannel {
+                    connection_id,
+                    counterparty_port_id,
+                },
+            };
+            execute(deps, mock_env(), mock_info(sender, &[]), msg)
+        };
+        let open = |deps: DepsMut, channel: IbcChannel| {
+            ibc_channel_open(
+                deps,
+                mock_env(),
+                IbcChannelOpenMsg::new_try(channel, IBC_VERSION),
+            )
+        };
+        let connect = |deps: DepsMut, channel: IbcChannel| {
+            ibc_channel_connect(
+                deps,
+                mock_env(),
+                IbcChannelConnectMsg::new_ack(channel, IBC_VERSION),
+            )
+        };
+        let channels = |deps: Deps| {
+            let res: AuthorizedChannelsResponse =
+                from_binary(&query(deps, mock_env(), QueryMsg::AuthorizedChannels {}).unwrap())
+                    .unwrap();
+            res.channels
+        };
+
+        // Nothing may connect before the owner expects it.
+        let err = open(deps.as_mut(), channel()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Channel to their-port over connection-2 is not authorized"
+        );
+        let err = connect(deps.as_mut(), channel()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedChannel { .. }));
+        let err = authorize(deps.as_mut(), "mallory", true).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        authorize(deps.as_mut(), "owner", true).unwrap();
+        assert_eq!(
+            channels(deps.as_ref()),
+            vec![AuthorizedChannel {
+                connection_id: "connection-2".to_string(),
+                counterparty_port_id: "their-port".to_string(),
+            }]
+        );
+
+        // Other ports and connections stay locked out.
+        let mut other_port = channel();
+        other_port.counterparty_endpoint.port_id = "wasm.attacker".to_string();
+        let err = open(deps.as_mut(), other_port).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedChannel { .. }));
+        let mut other_connection = channel();
+        other_connection.connection_id = "connection-9".to_string();
+        let err = connect(deps.as_mut(), other_connection).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedChannel { .. }));
+        open(deps.as_mut(), channel()).unwrap();
+        connect(deps.as_mut(), channel()).unwrap();
+
+        // Packets must come from the bound counterparty.
+        let mut packet =
+            mock_ibc_packet_recv("channel-0", &envelope(PacketMsg::CloseRequest { id: 0 }))
+                .unwrap();
+        packet.packet.src.channel_id = "channel-666".to_string();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), packet).unwrap();
+        let ack: StdAck = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            ContractError::UnboundChannel {}.to_string()
+        );
+        let packet =
+            mock_ibc_packet_recv("channel-0", &envelope(PacketMsg::CloseRequest { id: 0 }))
+                .unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), packet).unwrap();
+        assert_eq!(res.acknowledgement, StdAck::success(0));
+
+        // Revoking does not unbind the channel.
+        authorize(deps.as_mut(), "owner", false).unwrap();
+        assert!(channels(deps.as_ref()).is_empty());
+        let config: State =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.endpoint.unwrap().channel_id, "channel-0");
+    }
+
+    #[test]
+    fn test_swap_hooks() {
+        let mut deps = setup();
+        // "rewards" is a contract, "alice" an account.
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { contract_addr } if contract_addr == "rewards" => {
+                let info = cosmwasm_std::ContractInfoResponse::new(1, "creator");
+                SystemResult::Ok(ContractResult::Ok(to_binary(&info).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::NoSuchContract {
+                addr: "alice".to_string(),
+            }),
+        });
+        let add_hook = |deps: DepsMut, sender: &str, addr: &str, funds: &[Coin]| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, funds),
+                ExecuteMsg::AddHook {
+                    addr: addr.to_string(),
+                },
+            )
+        };
+        // Without a fee only the owner registers hooks.
+        let err = add_hook(deps.as_mut(), "rewards", "rewards", &[]).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        add_hook(deps.as_mut(), "owner", "points", &[]).unwrap();
+        let err = add_hook(deps.as_mut(), "owner", "points", &[]).unwrap_err();
+        assert!(matches!(err, ContractError::Hook(_)));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetHookFee {
+                fee: Some(coin(10, "uosmo")),
+            },
+        )
+        .unwrap();
+        let err = add_hook(deps.as_mut(), "alice", "alice", &coins(10, "uosmo")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = add_hook(deps.as_mut(), "rewards", "points", &coins(10, "uosmo")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = add_hook(deps.as_mut(), "rewards", "rewards", &coins(5, "uosmo")).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = add_hook(deps.as_mut(), "rewards", "rewards", &coins(10, "uosmo")).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "owner".to_string(),
+                amount: coins(10, "uosmo"),
+            }
+            .into()
+        );
+        let hooks: cw_controllers::HooksResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Hooks {}).unwrap()).unwrap();
+        assert_eq!(hooks.hooks, vec!["points", "rewards"]);
+
+        // Every hook is notified in a submessage that may fail.
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap();
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        let hook_msg = to_binary(&SwapHookExecuteMsg::SwapHook(SwapHookMsg::SwapCreated {
+            id: 0,
+            side: "A".to_string(),
+            swap,
+        }))
+        .unwrap();
+        let hook_msgs: Vec<_> = res.messages[1..].to_vec();
+        assert_eq!(
+            hook_msgs,
+            ["points", "rewards"].map(|hook| SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: hook.to_string(),
+                    msg: hook_msg.clone(),
+                    funds: vec![],
+                },
+                HOOK_REPLY_ID,
+            )
+            .with_gas_limit(DEFAULT_HOOK_GAS_LIMIT))
+        );
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: HOOK_REPLY_ID,
+                result: SubMsgResult::Err("out of points".to_string()),
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        // Hooks may remove themselves, others need the owner.
+        let remove_hook = |deps: DepsMut, sender: &str, addr: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::RemoveHook {
+                    addr: addr.to_string(),
+                },
+            )
+        };
+        let err = remove_hook(deps.as_mut(), "rewards", "points").unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        remove_hook(deps.as_mut(), "rewards", "rewards").unwrap();
+        remove_hook(deps.as_mut(), "owner", "points").unwrap();
+        let hooks: cw_controllers::HooksResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Hooks {}).unwrap()).unwrap();
+        assert!(hooks.hooks.is_empty());
+    }
+
+    #[test]
+    fn test_swap_hook_limits() {
+        let mut deps = setup();
+        let set_gas_limit = |deps: DepsMut, sender: &str| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SetHookGasLimit { gas_limit: 100_000 },
+            )
+        };
+        let err = set_gas_limit(deps.as_mut(), "mallory").unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        set_gas_limit(deps.as_mut(), "owner").unwrap();
+        let gas_limit: u64 =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::HookGasLimit {}).unwrap())
+                .unwrap();
+        assert_eq!(gas_limit, 100_000);
+
+        // The number of hooks is capped, even for the owner.
+        let add_hook = |deps: DepsMut, addr: String| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("owner", &[]),
+                ExecuteMsg::AddHook { addr },
+            )
+        };
+        for i in 0..MAX_HOOKS {
+            add_hook(deps.as_mut(), format!("hook{}", i)).unwrap();
+        }
+        let err = add_hook(deps.as_mut(), "hook".to_string()).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::TooManyHooks { max: MAX_HOOKS }
+        ));
+
+        // Settling over IBC runs every hook under the gas limit.
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        let accept = envelope(PacketMsg::AcceptSideA {
+            id: 0,
+            sender: "taker".to_string(),
+            price: None,
+            recipient: None,
+        });
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.acknowledgement, StdAck::success(0));
+        let hooks: Vec<_> = res
+            .messages
+            .iter()
+            .filter(|msg| msg.id == HOOK_REPLY_ID)
+            .collect();
+        assert_eq!(hooks.len(), MAX_HOOKS);
+        assert!(hooks
+            .iter()
+            .all(|msg| msg.gas_limit == Some(100_000) && msg.reply_on == ReplyOn::Error));
+
+        // A hook running out of gas is ignored like any other failure.
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: HOOK_REPLY_ID,
+                result: SubMsgResult::Err(
+                    "out of gas in location: wasm contract; gasWanted: 100000: out of gas"
+                        .to_string(),
+                ),
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        // Side A: deposits over the limit are refused with an error ack
+        // until the window rolls past earlier payouts.
+        let mut deps = setup();
+        set_rate_limit(deps.as_mut(), "channel-1", "ujuno", 150);
+        set_rate_limit(deps.as_mut(), "channel-3", "ujuno", 150);
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        create_swap(deps.as_mut(), "maker", "ujuno", 100);
+        // Paid out over another transfer channel, with its own quota.
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-3".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap();
+        let accept = |id: u64| {
+            envelope(PacketMsg::AcceptSideA {
+                id,
+                sender: "taker".to_string(),
+                price: None,
+                recipient: None,
+            })
+        };
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept(0)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept(1)).unwrap(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            res.acknowledgement,
+            StdAck::fail(
+                ContractError::RateLimited {
+                    channel_id: "channel-1".to_string(),
+                    denom: "ujuno".to_string(),
+                }
+                .to_string()
+            )
+        );
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept(2)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            env_at(200),
+            mock_ibc_packet_recv("channel-0", &accept(1)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        // Side B: inbound listings count against the channel they
+        // arrive on, whatever transfer channel they name.
+        let mut deps = setup();
+        set_rate_limit(deps.as_mut(), "channel-0", "ujuno", 150);
+        set_rate_limit(deps.as_mut(), "channel-2", "uosmo", 300);
+        receive_swap(deps.as_mut(), 0, "maker", "ujuno", "uosmo");
+        receive_swap(deps.as_mut(), 1, "maker", "ujuno", "uosmo");
+        receive_swap(deps.as_mut(), 2, "maker", "uatom", "uosmo");
+        assert!(swaps_b().has(&deps.storage, 0));
+        assert!(!swaps_b().has(&deps.storage, 1));
+        let mut swap = swaps_b().load(&deps.storage, 0).unwrap();
+        swap.status = SwapStatus::Pending;
+        swap.deposit_transfer_channel_id = "channel-9".to_string();
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv(
+                "channel-0",
+                &envelope(PacketMsg::CreateSideB { id: 3, swap }),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.acknowledgement,
+            StdAck::fail(
+                ContractError::RateLimited {
+                    channel_id: "channel-0".to_string(),
+                    denom: "ujuno".to_string(),
+                }
+                .to_string()
+            )
+        );
+        assert!(!swaps_b().has(&deps.storage, 3));
+
+        // Payouts over the limit are queued instead.
+        for id in [0, 2] {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("taker", &coins(200, "uosmo")),
+                ExecuteMsg::AcceptSwap {
+                    id,
+                    recipient: None,
+                    local: false,
+                },
+            )
+            .unwrap();
+        }
+        let ack = |deps: DepsMut, id: u64| {
+            ibc_packet_ack(
+                deps,
+                mock_env(),
+                mock_ibc_packet_ack(
+                    "channel-0",
+                    &accept(id),
+                    IbcAcknowledgement::new(StdAck::success(id)),
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(ack(deps.as_mut(), 0).messages.len(), 1);
+        assert!(ack(deps.as_mut(), 2).messages.is_empty());
+        let queued: ListSwapsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::QueuedPayouts {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(queued.swaps.len(), 1);
+        assert_eq!(queued.swaps[0].id, 2);
+        let usage: RateLimitResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::RateLimit {
+                    channel_id: "channel-2".to_string(),
+                    denom: "uosmo".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(usage.outbound, Uint128::new(200));
+        assert_eq!(usage.inbound, Uint128::zero());
+
+        let release = ExecuteMsg::ReleasePayout { id: 2 };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            release.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RateLimited { .. }));
+        let res = execute(
+            deps.as_mut(),
+            env_at(200),
+            mock_info("anyone", &[]),
+            release,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-2".to_string(),
+                to_address: "maker".to_string(),
+                amount: coin(200, "uosmo"),
+                timeout: env_at(200).block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+        assert!(!PAYOUTS.has(&deps.storage, 2));
+    }
+
+    fn create_htlc_swap(deps: DepsMut, preimage: &[u8]) -> Response {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: Some(HtlcMsg {
+                    hashlock: HexBinary::from(Sha256::digest(preimage).to_vec()),
+                    timelock: 3600,
+                }),
+                dutch_auction: None,
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap()
+    }
+
+    fn env_at(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    #[test]
+    fn test_htlc_swap() {
+        let secret = HexBinary::from(b"secret".to_vec());
+        let wrong = HexBinary::from(b"wrong".to_vec());
+
+        // Side A: the deposit stays locked until the preimage is relayed.
+        let mut deps = setup();
+        create_htlc_swap(deps.as_mut(), &secret);
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        let htlc = swap.htlc.clone().unwrap();
+        assert_eq!(htlc.maker_expires, mock_env().block.time.plus_seconds(3600));
+        assert_eq!(htlc.taker_expires, mock_env().block.time.plus_seconds(1800));
+
+        let accept = envelope(PacketMsg::AcceptSideA {
+            id: 0,
+            sender: "taker".to_string(),
+            price: None,
+            recipient: None,
+        });
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept).unwrap(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(swap.status, SwapStatus::Accepted);
+        assert_eq!(swap.taker, Some(Addr::unchecked("taker")));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ClaimSwap {
+                id: 0,
+                preimage: wrong,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPreimage {}));
+
+        let reveal = envelope(PacketMsg::RevealSideA {
+            id: 0,
+            preimage: secret.clone(),
+        });
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &reveal).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-1".to_string(),
+                to_address: "taker".to_string(),
+                amount: coin(100, "ujuno"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+        assert!(swaps_a().may_load(&deps.storage, 0).unwrap().is_none());
+
+        // Side A: an unaccepted deposit goes back to the maker after the time lock.
+        create_htlc_swap(deps.as_mut(), &secret);
+        let refund = ExecuteMsg::RefundSwap {
+            side: "A".to_string(),
+            id: 1,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env_at(3599),
+            mock_info("anyone", &[]),
+            refund.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SwapNotExpired {}));
+        // Nothing runs at expiry, any account may trigger the refund
+        // and it still goes to the maker.
+        assert!(swaps_a().has(&deps.storage, 1));
+        let res = execute(
+            deps.as_mut(),
+            env_at(3600),
+            mock_info("anyone", &[]),
+            refund,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "maker".to_string(),
+                amount: coins(100, "ujuno"),
+            }
+            .into()
+        );
+        assert_eq!(res.events[0].ty, "swap_refunded");
+        assert_eq!(stats(deps.as_ref()).stats.swaps_refunded, 1);
+
+        // Side B: the maker collects the payment by revealing the preimage.
+        let listing = envelope(PacketMsg::CreateSideB {
+            id: 0,
+            swap: Swap {
+                status: SwapStatus::Pending,
+                ..swap.clone()
+            },
+        });
+        let mut deps = setup();
+        for id in 0..2 {
+            let mut listing = listing.clone();
+            if let PacketMsg::CreateSideB { id: listing_id, .. } = &mut listing.msg {
+                *listing_id = id;
+            }
+            ibc_packet_receive(
+                deps.as_mut(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &listing).unwrap(),
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("taker", &coins(200, "uosmo")),
+                ExecuteMsg::AcceptSwap {
+                    id,
+                    recipient: None,
+                    local: false,
+                },
+            )
+            .unwrap();
+        }
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accept,
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &[]),
+            ExecuteMsg::RevealPreimage {
+                id: 0,
+                preimage: secret.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[1].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &reveal.msg).unwrap(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // Side B: past its time lock the preimage is refused and the
+        // taker is refunded instead.
+        let err = execute(
+            deps.as_mut(),
+            env_at(1800),
+            mock_info("maker", &[]),
+            ExecuteMsg::RevealPreimage {
+                id: 1,
+                preimage: secret,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SwapExpired {}));
+        let res = execute(
+            deps.as_mut(),
+            env_at(1800),
+            mock_info("anyone", &[]),
+            ExecuteMsg::RefundSwap {
+                side: "B".to_string(),
+                id: 1,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(200, "uosmo"),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_dutch_auction() {
+        // Side A: the ask declines from 200 to 100 over 100 seconds.
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker", &coins(100, "ujuno")),
+            ExecuteMsg::CreateSwap {
+                ask: token("uosmo", 200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+                htlc: None,
+                dutch_auction: Some(DutchAuctionMsg {
+                    floor: Uint128::new(100),
+                    duration: 100,
+                }),
+                english_auction: None,
+                recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            },
+        )
+        .unwrap();
+        let current_ask = |deps: Deps, side: &str, seconds| -> Token {
+            let msg = QueryMsg::CurrentAsk {
+                side: side.to_string(),
+                id: 0,
+            };
+            from_binary(&query(deps, env_at(seconds), msg).unwrap()).unwrap()
+        };
+        assert_eq!(current_ask(deps.as_ref(), "A", 25), token("uosmo", 175));
+        assert_eq!(current_ask(deps.as_ref(), "A", 500), token("uosmo", 100));
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+
+        // A price below the floor is refused.
+        let accept = |price| {
+            envelope(PacketMsg::AcceptSideA {
+                id: 0,
+                sender: "taker".to_string(),
+                price: Some(Uint128::new(price)),
+                recipient: None,
+            })
+        };
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept(99)).unwrap(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept(150)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "ask_amount" && attr.value == "150"));
+
+        // Side B: the taker pays the price at accept time and gets the rest back.
+        let mut deps = setup();
+        let listing = envelope(PacketMsg::CreateSideB { id: 0, swap });
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &listing).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(current_ask(deps.as_ref(), "B", 50), token("uosmo", 150));
+        let err = execute(
+            deps.as_mut(),
+            env_at(50),
+            mock_info("taker", &coins(149, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = execute(
+            deps.as_mut(),
+            env_at(50),
+            mock_info("taker", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "taker".to_string(),
+                amount: coins(50, "uosmo"),
+            }
+            .into()
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &accept(150).msg).unwrap(),
+                timeout: env_at(50).block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // The accepted price no longer declines and is what the maker is paid.
+        assert_eq!(current_ask(deps.as_ref(), "B", 100), token("uosmo", 150));
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accept(150),
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-2".to_string(),
+                to_address: "maker".to_string(),
+                amount: coin(150, "uosmo"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_english_auction() {
+        // Side A: the deposit is auctioned for 100 seconds with a reserve of 200.
+        let mut deps = setup();
+        for _ in 0..2 {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("maker", &coins(100, "ujuno")),
+                ExecuteMsg::CreateSwap {
+                    ask: token("uosmo", 200),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                    htlc: None,
+                    dutch_auction: None,
+                    english_auction: Some(EnglishAuctionMsg { duration: 100 }),
+                    recipient: None,
+                    refund_address: None,
+                    allowed_takers: None,
+                    local: false,
+                },
+            )
+            .unwrap();
+        }
+        let swap = swaps_a().load(&deps.storage, 0).unwrap();
+        assert_eq!(
+            swap.english_auction.as_ref().unwrap().deadline,
+            env_at(100).block.time
+        );
+
+        // Side B: bids must meet the reserve, then beat the highest bid.
+        let mut deps_b = setup();
+        for id in 0..2 {
+            let listing = envelope(PacketMsg::CreateSideB {
+                id,
+                swap: swap.clone(),
+            });
+            ibc_packet_receive(
+                deps_b.as_mut(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &listing).unwrap(),
+            )
+            .unwrap();
+        }
+        let bid = |deps: DepsMut, bidder: &str, amount: u128, seconds: u64| {
+            execute(
+                deps,
+                env_at(seconds),
+                mock_info(bidder, &coins(amount, "uosmo")),
+                ExecuteMsg::PlaceBid { id: 0 },
+            )
+        };
+        let err = execute(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(200, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 0,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::AuctionSwap {}));
+        let err = bid(deps_b.as_mut(), "bob", 199, 10).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let err = execute(
+            deps_b.as_mut(),
+            env_at(10),
+            mock_info("bob", &coins(200, "ujuno")),
+            ExecuteMsg::PlaceBid { id: 0 },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::MissingDenom(_))
+        ));
+        let res = bid(deps_b.as_mut(), "bob", 200, 10).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.events[0].ty, "swap_bid");
+        assert_solvent(&mut deps_b, &coins(200, "uosmo"), &res.messages);
+        let err = bid(deps_b.as_mut(), "carol", 200, 20).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = bid(deps_b.as_mut(), "carol", 250, 20).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(200, "uosmo"),
+            }
+            .into()
+        );
+        assert_solvent(&mut deps_b, &coins(250, "uosmo"), &res.messages);
+        let err = bid(deps_b.as_mut(), "bob", 300, 100).unwrap_err();
+        assert!(matches!(err, ContractError::AuctionEnded {}));
+
+        // Anyone finalizes after the deadline, accepting the winning bid.
+        let finalize = |deps: DepsMut, id, seconds| {
+            execute(
+                deps,
+                env_at(seconds),
+                mock_info("anyone", &[]),
+                ExecuteMsg::FinalizeAuction { id },
+            )
+        };
+        let err = finalize(deps_b.as_mut(), 0, 99).unwrap_err();
+        assert!(matches!(err, ContractError::AuctionRunning {}));
+        let res = finalize(deps_b.as_mut(), 0, 100).unwrap();
+        let accept = envelope(PacketMsg::AcceptSideA {
+            id: 0,
+            sender: "carol".to_string(),
+            price: Some(Uint128::new(250)),
+            recipient: None,
+        });
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &accept.msg).unwrap(),
+                timeout: env_at(160).block.time.into(),
+            }
+            .into()
+        );
+        let res = ibc_packet_ack(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack(
+                "channel-0",
+                &accept,
+                IbcAcknowledgement::new(StdAck::success(0)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-2".to_string(),
+                to_address: "maker".to_string(),
+                amount: coin(250, "uosmo"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+        assert_solvent(&mut deps_b, &[], &res.messages);
+        // Both bids came in, the outbid one was refunded.
+        let res = stats(deps_b.as_ref());
+        assert_eq!(res.stats.bids_refunded, 1);
+        assert_eq!(
+            res.volume,
+            vec![
+                DenomVolume {
+                    direction: "in".to_string(),
+                    denom: "uosmo".to_string(),
+                    amount: Uint128::new(450),
+                },
+                DenomVolume {
+                    direction: "out".to_string(),
+                    denom: "uosmo".to_string(),
+                    amount: Uint128::new(250),
+                },
+                DenomVolume {
+                    direction: "refunded".to_string(),
+                    denom: "uosmo".to_string(),
+                    amount: Uint128::new(200),
+                },
+            ]
+        );
+
+        // Side A releases the deposit to the winner.
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &accept).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::Transfer {
+                channel_id: "channel-1".to_string(),
+                to_address: "carol".to_string(),
+                amount: coin(100, "ujuno"),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // An auction without bids is withdrawn and refunded on side A.
+        let res = finalize(deps_b.as_mut(), 1, 100).unwrap();
+        assert!(swaps_b().may_load(&deps_b.storage, 1).unwrap().is_none());
+        let refund = envelope(PacketMsg::RefundSideA { id: 1 });
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &refund.msg).unwrap(),
+                timeout: env_at(160).block.time.into(),
+            }
+            .into()
+        );
+        let res = ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &refund).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "maker".to_string(),
+                amount: coins(100, "ujuno"),
+            }
+            .into()
+        );
+        assert_eq!(stats(deps.as_ref()).stats.swaps_refunded, 1);
+    }
+
+    #[test]
+    fn test_request_for_quote() {
+        // Side B: the requester wants 100 ujuno and pays in uosmo.
+        let mut deps_b = setup();
+        let res = execute(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_info("requester", &[]),
+            ExecuteMsg::RequestQuote {
+                want: token("ujuno", 100),
+                pay_denom: Denom::Native("uosmo".to_string()),
+            },
+        )
+        .unwrap();
+        let rfq = RFQS_B.load(&deps_b.storage, 0).unwrap();
+        let request = envelope(PacketMsg::RequestQuote { id: 0, rfq });
+        assert_eq!(
+            res.messages[0].msg,
+            IbcMsg::SendPacket {
+                channel_id: "channel-0".to_string(),
+                data: encode_packet(Some(IBC_VERSION), &request.msg).unwrap(),
+                timeout: mock_env().block.time.plus_seconds(60).into(),
+            }
+            .into()
+        );
+
+        // Side A: makers quote by escrowing exactly what was requested.
+        let mut deps = setup();
+        ibc_packet_receive(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &request).unwrap(),
+        )
+        .unwrap();
+        let requests: ListRequestsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListRequests {
+                    side: "A".to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(requests.requests.len(), 1);
+        let quote = |deps: DepsMut, maker: &str, amount, price| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(maker, &coins(amount, "ujuno")),
+                ExecuteMsg::SubmitQuote {
+                    rfq_id: 0,
+                    price: Uint128::new(price),
+                    deposit_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                },
+            )
+        };
+        let err = quote(deps.as_mut(), "alice", 90, 200).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidQuote {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitQuote {
+                rfq_id: 0,
+                price: Uint128::new(200),
+                deposit_transfer_channel_id: "channel-1".to_string(),
+                ask_transfer_channel_id: "channel-2".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Payment(PaymentError::NoFunds {})
+        ));
+        for (maker, price) in [("alice", 200), ("bob", 180), ("carol", 190)] {
+            quote(deps.as_mut(), maker, 100, price).unwrap();
+        }
+        let quoted = swaps_a().load(&deps.storage, 1).unwrap();
+        assert_eq!(quoted.ask, token("uosmo", 180));
+        assert_eq!(quoted.ask_address, Some(Addr::unchecked("requester")));
+
+        // Side B lists the first two quotes for the requester.
+        for id in 0..2 {
+            let listing = envelope(PacketMsg::CreateSideB {
+                id,
+                swap: swaps_a().load(&deps.storage, id).unwrap(),
+            });
+            ibc_packet_receive(
+                deps_b.as_mut(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &listing).unwrap(),
+            )
+            .unwrap();
+        }
+        let quotes = QueryMsg::Quotes {
+            rfq_id: 0,
+            start_after: None,
+            limit: None,
+        };
+        assert_eq!(list_ids(deps_b.as_ref(), quotes), vec![0, 1]);
+
+        // Quotes are picked through their request, by the requester only.
+        let err = execute(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_info("requester", &coins(180, "uosmo")),
+            ExecuteMsg::AcceptSwap {
+                id: 1,
+                recipient: None,
+                local: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::QuoteSwap {}));
+        let err = execute(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_info("mallory", &coins(180, "uosmo")),
+            ExecuteMsg::AcceptQuote { id: 1 },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = execute(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_info("requester", &coins(180, "uosmo")),
+            ExecuteMsg::AcceptQuote { id: 1 },
+        )
+        .unwrap();
+        let packets: Vec<PacketMsg> = res
+            .messages
+            .iter()
+            .map(|msg| match &msg.msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => {
+                    from_binary::<PacketEnvelope>(data).unwrap().msg
+                }
+                msg => panic!("unexpected message {:?}", msg),
+            })
+            .collect();
+        assert_eq!(
+            packets,
+            vec![
+                PacketMsg::AcceptSideA {
+                    id: 1,
+                    sender: "requester".to_string(),
+                    price: None,
+                    recipient: None,
+                },
+                PacketMsg::CloseRequest { id: 0 },
+                PacketMsg::RefundSideA { id: 0 },
+            ]
+        );
+        assert!(RFQS_B.may_load(&deps_b.storage, 0).unwrap().is_none());
+
+        // A quote arriving after the request closed is refused ...
+        let late = envelope(PacketMsg::CreateSideB {
+            id: 2,
+            swap: swaps_a().load(&deps.storage, 2).unwrap(),
+        });
+        let res = ibc_packet_receive(
+            deps_b.as_mut(),
+            mock_env(),
+            mock_ibc_packet_recv("channel-0", &late).unwrap(),
+        )
+        .unwrap();
+        let ack = res.acknowledgement.clone();
+        assert!(swaps_b().may_load(&deps_b.storage, 2).unwrap().is_none());
+
+        // ... and refunded on side A, like the withdrawn quote.
+        let res = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            mock_ibc_packet_ack("channel-0", &late, IbcAcknowledgement::new(ack)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "carol".to_string(),
+                amount: coins(100, "ujuno"),
+            }
+            .into()
+        );
+        for packet in [packets[1].clone(), packets[2].clone()] {
+            ibc_packet_receive(
+                deps.as_mut(),
+                mock_env(),
+                mock_ibc_packet_recv("channel-0", &envelope(packet)).unwrap(),
+            )
+            .unwrap();
         }
+        assert!(RFQS_A.is_empty(&deps.storage));
+        assert_eq!(stats(deps.as_ref()).stats.swaps_refunded, 2);
+        assert_eq!(
+            swaps_a().load(&deps.storage, 1).unwrap().status,
+            SwapStatus::Pending
+        );
     }
 }
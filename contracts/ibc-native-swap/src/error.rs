@@ -1,4 +1,6 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, StdError};
+use cw_controllers::HookError;
+use cw_utils::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,13 +8,31 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Hook(#[from] HookError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
     #[error("Unauthorized")]
     Unauthorized {},
+
+    #[error("At most {max} hooks may be registered")]
+    TooManyHooks { max: usize },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
     #[error("Already Connected")]
     AlreadyConnected {},
 
+    #[error("Channel to {port_id} over {connection_id} is not authorized")]
+    UnauthorizedChannel {
+        connection_id: String,
+        port_id: String,
+    },
+
+    #[error("Packet did not arrive over the bound channel")]
+    UnboundChannel {},
+
     #[error("Not Connected")]
     NotConnected {},
 
@@ -22,14 +42,149 @@ pub enum ContractError {
     #[error("invalid IBC channel version - got ({actual}), expected ({expected})")]
     InvalidVersion { actual: String, expected: String },
 
+    #[error("Swap terms are not supported over a legacy channel")]
+    LegacyChannel {},
+
     #[error("channels may not be closed")]
     CantCloseChannel {},
 
     #[error("Insufficient funds")]
     InsufficientFunds {},
+
+    #[error("cw20 tokens are not supported")]
+    Cw20Unsupported {},
+
+    #[error("Swap is not open")]
+    SwapNotOpen {},
+
+    #[error("Swap has not been accepted")]
+    SwapNotAccepted {},
+
+    #[error("Swap has no hash lock")]
+    NotHtlc {},
+
+    #[error("Hash lock must be a 32 byte sha256 digest")]
+    InvalidHashlock {},
+
+    #[error("Time lock must be at least {min} seconds")]
+    InvalidTimelock { min: u64 },
+
+    #[error("Auction floor must be below the ask and its duration positive")]
+    InvalidAuction {},
+
+    #[error("Price is below the auction floor")]
+    InvalidPrice {},
+
+    #[error("Swap modes can not be combined")]
+    IncompatibleModes {},
+
+    #[error("Swap is not an auction")]
+    NotAuction {},
+
+    #[error("Auction swaps are settled by bidding")]
+    AuctionSwap {},
+
+    #[error("Auction has ended")]
+    AuctionEnded {},
+
+    #[error("Auction is still running")]
+    AuctionRunning {},
+
+    #[error("Quote must escrow exactly what was requested")]
+    InvalidQuote {},
+
+    #[error("Request for quote is closed")]
+    RequestClosed {},
+
+    #[error("Quotes are accepted through their request")]
+    QuoteSwap {},
+
+    #[error("Denom {denom} can not be swapped")]
+    DenomNotAllowed { denom: String },
+
+    #[error("Amount of {denom} is outside the allowed swap size")]
+    InvalidSwapSize { denom: String },
+
+    #[error("Rate limit for {denom} on {channel_id} exceeded")]
+    RateLimited { channel_id: String, denom: String },
+
+    #[error("Rate limit window must be positive")]
+    InvalidRateLimit {},
+
+    #[error(
+        "Swap price deviates from the oracle price ({oracle_price}) by more than the tolerance"
+    )]
+    PriceDeviation { oracle_price: Decimal },
+
+    #[error("Price oracle has no price for {base_denom} in {quote_denom}")]
+    NoOraclePrice {
+        base_denom: String,
+        quote_denom: String,
+    },
+
+    #[error("No ICS-721 bridge configured")]
+    NoNftBridge {},
+
+    #[error("NFT does not match the swap")]
+    InvalidNft {},
+
+    #[error("NFT collection {collection} is not allowed")]
+    NftCollectionNotAllowed { collection: String },
+
+    #[error("Taker allowlist is empty")]
+    EmptyAllowlist {},
+
+    #[error("No position NFT contract configured")]
+    NoPositionNft {},
+
+    #[error("Swap is already tokenized")]
+    AlreadyTokenized {},
+
+    #[error("No swap router configured")]
+    NoSwapRouter {},
+
+    #[error("Route must swap another denom into the ask")]
+    InvalidRoute {},
+
+    #[error("Batch funds must add up to the deposits of its swaps")]
+    InvalidBatchFunds {},
+
+    #[error("Swap is settled on this chain only")]
+    LocalSwap {},
+
+    #[error("Swap is not a local swap")]
+    NotLocal {},
+
+    #[error("Makers with a refund address must name a recipient")]
+    RecipientRequired {},
+
+    #[error("Invalid recipient ({recipient}), expected a bech32 address on the payee's chain")]
+    InvalidRecipient { recipient: String },
+
+    #[error("Preimage does not match the hash lock")]
+    InvalidPreimage {},
+
+    #[error("Swap time lock has expired")]
+    SwapExpired {},
+
+    #[error("Swap time lock has not expired yet")]
+    SwapNotExpired {},
+
+    #[error("Cannot migrate from a different contract ({previous_contract})")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from newer version ({previous_version}) to older ({new_version})")]
+    CannotMigrateVersion {
+        previous_version: String,
+        new_version: String,
+    },
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
 }
 
-/// Enum that can never be constructed. Used as an error type where we
-/// can not error.
-#[derive(Error, Debug)]
-pub enum Never {}
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
+}
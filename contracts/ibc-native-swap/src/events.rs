@@ -0,0 +1,81 @@
+//! Wasm events emitted on every swap transition. Each event carries
+//! the same attributes so indexers can follow a swap across both
+//! chains by its `id`:
+//!
+//! | attribute      | value                                             |
+//! |----------------|---------------------------------------------------|
+//! | `id`           | swap id, shared by side A and side B              |
+//! | `side`         | `A` on the maker's chain, `B` on the taker's      |
+//! | `channel`      | IBC channel the swap's packets travel over, or    |
+//! |                | `local` for swaps that never leave side A         |
+//! | `maker`        | address that escrowed the deposit on side A       |
+//! | `taker`        | accepting or designated taker, omitted if unknown |
+//! | `deposit_denom`, `deposit_amount` | what the maker offers          |
+//! | `ask_denom`, `ask_amount`         | what the maker wants in return |
+//!
+//! Events, in lifecycle order:
+//!
+//! - `swap_created`: side A escrowed the deposit and sent the listing,
+//!   or listed a local swap. Carries the `oracle_price` if the swap's
+//!   price deviates from the price oracle's beyond its tolerance.
+//! - `swap_listed`: side B stored the listing, or side A received the
+//!   acknowledgement that it did.
+//! - `swap_bid`: side B escrowed a new highest bid on an auction swap,
+//!   with the bidder as `taker` and the bid as `ask_amount`.
+//! - `swap_accepted`: side B escrowed the taker's payment, or side A
+//!   recorded the taker of an HTLC swap.
+//! - `swap_settled`: side A released the deposit to the taker, or
+//!   side B released the payment to the maker. Local swaps release
+//!   both in the transaction that accepts them.
+//! - `swap_refunded`: escrowed funds were returned to their owner once
+//!   an HTLC swap's time lock expired, an auction closed without bids,
+//!   the maker or position holder cancelled the swap, or a packet that
+//!   would have settled them failed or timed out.
+
+use cosmwasm_std::Event;
+
+use crate::state::{denom_key, Swap};
+
+pub const SWAP_CREATED: &str = "swap_created";
+pub const SWAP_LISTED: &str = "swap_listed";
+pub const SWAP_BID: &str = "swap_bid";
+pub const SWAP_ACCEPTED: &str = "swap_accepted";
+pub const SWAP_SETTLED: &str = "swap_settled";
+pub const SWAP_REFUNDED: &str = "swap_refunded";
+
+/// `channel` attribute of local swaps.
+pub const LOCAL_CHANNEL: &str = "local";
+
+/// Builds a swap transition event following the schema above.
+pub fn swap_event(
+    ty: &str,
+    id: u64,
+    side: &str,
+    channel: &str,
+    swap: &Swap,
+    taker: Option<&str>,
+) -> Event {
+    let taker = taker.map(str::to_string).or_else(|| {
+        swap.taker
+            .as_ref()
+            .or(swap.ask_address.as_ref())
+            .map(|addr| addr.to_string())
+    });
+
+    // Empty attribute values are rejected by the SDK, so an unknown
+    // taker is left out rather than sent as "".
+    let event = Event::new(ty)
+        .add_attribute("id", id.to_string())
+        .add_attribute("side", side)
+        .add_attribute("channel", channel)
+        .add_attribute("maker", swap.deposit_address.as_str());
+    let event = match taker {
+        Some(taker) => event.add_attribute("taker", taker),
+        None => event,
+    };
+    event
+        .add_attribute("deposit_denom", denom_key(&swap.deposit.denom))
+        .add_attribute("deposit_amount", swap.deposit.amount)
+        .add_attribute("ask_denom", denom_key(&swap.ask.denom))
+        .add_attribute("ask_amount", swap.ask.amount)
+}
@@ -1,29 +1,47 @@
 use cosmwasm_std::{
-    entry_point, from_slice, Coin, DepsMut, Env, IbcBasicResponse, IbcChannelCloseMsg,
-    IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse, IbcMsg, IbcPacketAckMsg,
-    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult,
+    entry_point, Addr, CosmosMsg, Deps, DepsMut, Env, Event, HexBinary, Ibc3ChannelOpenResponse,
+    IbcBasicResponse, IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcChannelOpenResponse, IbcMsg, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
+    IbcReceiveResponse, SubMsg, Uint128,
 };
-use cw20::Denom;
 
-//use crate::error::Never;
-
-use crate::ibc_helpers::{validate_order_and_version, StdAck};
+use crate::ibc_helpers::{
+    decode_packet, encode_packet, negotiate_version, try_get_ack_error, try_get_ack_payee,
+    validate_order_and_version, StdAck,
+};
 
+use crate::contract::execute::{
+    check_denom, pay_maker, position_holder, release_deposit, send_token, swap_hooks,
+    transfer_token,
+};
 use crate::error::ContractError;
-use crate::msg::PacketMsg;
-use crate::state::{Swap, STATE, SWAPS_A, SWAPS_B};
+use crate::events::{swap_event, SWAP_ACCEPTED, SWAP_LISTED, SWAP_REFUNDED, SWAP_SETTLED};
+use crate::msg::{AcceptAck, PacketMsg, SwapHookMsg};
+use crate::state::{
+    consume_flow, denom_key, escrow_out, record_volume, swaps_a, swaps_b, update_stats, Payout,
+    Swap, SwapStatus, AUTHORIZED_CHANNELS, PAYOUTS, POSITIONS, RFQS_A, RFQS_B, STATE, VOLUME_IN,
+    VOLUME_OUT, VOLUME_REFUNDED,
+};
 //use crate::state::PENDING;
 
-pub const IBC_VERSION: &str = "native-swap-1";
+pub const IBC_VERSION_PREFIX: &str = "native-swap-";
+/// The highest protocol revision we speak, proposed when opening channels.
+pub const IBC_VERSION: &str = "native-swap-2";
+/// Revision that exchanges bare `PacketMsg`s rather than envelopes.
+pub const LEGACY_IBC_REVISION: u32 = 1;
+pub const MIN_IBC_REVISION: u32 = 1;
+pub const MAX_IBC_REVISION: u32 = 2;
 
 #[entry_point]
-/// enforces ordering and versioing constraints
+/// enforces ordering and negotiates the channel version
 pub fn ibc_channel_open(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     msg: IbcChannelOpenMsg,
 ) -> Result<IbcChannelOpenResponse, ContractError> {
-    validate_order_and_version(msg.channel(), msg.counterparty_version())
+    check_authorized(deps.as_ref(), msg.channel())?;
+    let version = negotiate_version(msg.channel(), msg.counterparty_version())?;
+    Ok(Some(Ibc3ChannelOpenResponse { version }))
 }
 
 #[entry_point]
@@ -33,7 +51,8 @@ pub fn ibc_channel_connect(
     _env: Env,
     msg: IbcChannelConnectMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    validate_order_and_version(msg.channel(), msg.counterparty_version())?;
+    check_authorized(deps.as_ref(), msg.channel())?;
+    let version = validate_order_and_version(msg.channel(), msg.counterparty_version())?;
 
     let mut state = STATE.load(deps.storage)?;
     if state.endpoint.is_some() {
@@ -46,13 +65,29 @@ pub fn ibc_channel_connect(
     }
 
     state.counterparty_endpoint = Some(msg.channel().counterparty_endpoint.clone());
+    state.ibc_version = Some(version.clone());
 
     STATE.save(deps.storage, &state)?;
 
     Ok(IbcBasicResponse::new()
         .add_attribute("method", "ibc_channel_connect")
         .add_attribute("channel", &msg.channel().endpoint.channel_id)
-        .add_attribute("port", &msg.channel().endpoint.port_id))
+        .add_attribute("port", &msg.channel().endpoint.port_id)
+        .add_attribute("version", version))
+}
+
+/// Only channels the owner expects may be opened, so no other contract
+/// can claim the single channel this one binds.
+fn check_authorized(deps: Deps, channel: &IbcChannel) -> Result<(), ContractError> {
+    let connection_id = &channel.connection_id;
+    let port_id = &channel.counterparty_endpoint.port_id;
+    if !AUTHORIZED_CHANNELS.has(deps.storage, (connection_id, port_id)) {
+        return Err(ContractError::UnauthorizedChannel {
+            connection_id: connection_id.clone(),
+            port_id: port_id.clone(),
+        });
+    }
+    Ok(())
 }
 
 #[entry_point]
@@ -84,31 +119,113 @@ pub fn ibc_packet_receive(
     env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let packet_msg: StdResult<PacketMsg> = from_slice(&msg.packet.data);
-    /*if packet_msg.is_err() {
+    let state = STATE.load(deps.storage)?;
+    let bound = match (&state.endpoint, &state.counterparty_endpoint) {
+        (Some(endpoint), Some(counterparty)) => {
+            msg.packet.dest.channel_id == endpoint.channel_id && msg.packet.src == *counterparty
+        }
+        _ => false,
+    };
+    if !bound {
         return Ok(IbcReceiveResponse::new()
             .add_attribute("method", "ibc_packet_receive")
-            .add_attribute("error", "invalid packet data")
-            .set_ack(StdAck::fail("invalid packet data".to_string())));
-    }*/
+            .set_ack(StdAck::fail(ContractError::UnboundChannel {}.to_string())));
+    }
 
-    match packet_msg.unwrap() {
+    let packet_msg = match decode_packet(state.ibc_version.as_deref(), &msg.packet.data) {
+        Ok(packet_msg) => packet_msg,
+        Err(err) => {
+            return Ok(IbcReceiveResponse::new()
+                .add_attribute("method", "ibc_packet_receive")
+                .add_attribute("error", "invalid packet data")
+                .set_ack(StdAck::fail(err.to_string())))
+        }
+    };
+
+    match packet_msg {
         PacketMsg::CreateSideB { id, swap } => create_side_b(deps, env, id, swap, msg),
-        PacketMsg::AcceptSideA { id, sender } => accept_side_a(deps, env, id, sender, msg),
+        PacketMsg::AcceptSideA {
+            id,
+            sender,
+            price,
+            recipient,
+        } => accept_side_a(deps, env, id, sender, price, recipient, msg),
+        PacketMsg::RevealSideA { id, preimage } => reveal_side_a(deps, env, id, preimage),
+        PacketMsg::RefundSideA { id } => refund_side_a(deps, id, msg),
+        PacketMsg::CancelSideB { id } => cancel_side_b(deps, id),
+        PacketMsg::RequestQuote { id, rfq } => {
+            RFQS_A.save(deps.storage, id, &rfq)?;
+            Ok(IbcReceiveResponse::new()
+                .add_attribute("method", "ibc_packet_receive")
+                .add_attribute("rfq_id", id.to_string())
+                .set_ack(StdAck::success(id)))
+        }
+        PacketMsg::CloseRequest { id } => {
+            RFQS_A.remove(deps.storage, id);
+            Ok(IbcReceiveResponse::new()
+                .add_attribute("method", "ibc_packet_receive")
+                .add_attribute("rfq_id", id.to_string())
+                .set_ack(StdAck::success(id)))
+        }
     }
 }
 
 pub fn create_side_b(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     id: u64,
-    swap: Swap,
-    _msg: IbcPacketReceiveMsg,
+    mut swap: Swap,
+    msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    SWAPS_B.save(deps.storage, id, &swap).unwrap();
+    // Listings this side's owner does not allow are refused, side A
+    // refunds them.
+    if let Err(err) =
+        check_denom(deps.storage, &swap.deposit).and_then(|_| check_denom(deps.storage, &swap.ask))
+    {
+        return Ok(IbcReceiveResponse::new()
+            .add_attribute("method", "ibc_packet_receive")
+            .set_ack(StdAck::fail(err.to_string())));
+    }
+
+    // Quotes are only listed while their request is open, side A
+    // refunds the rest.
+    if let Some(rfq_id) = swap.rfq_id {
+        match RFQS_B.may_load(deps.storage, rfq_id)? {
+            Some(rfq) if swap.ask_address.as_ref() == Some(&rfq.requester) => {}
+            _ => {
+                return Ok(IbcReceiveResponse::new()
+                    .add_attribute("method", "ibc_packet_receive")
+                    .set_ack(StdAck::fail(ContractError::RequestClosed {}.to_string())))
+            }
+        }
+    }
+
+    // Forged listings could otherwise be accepted without bound. They
+    // count against the channel they arrive on, as the transfer channel
+    // they name is the sender's to choose.
+    let channel_id = &msg.packet.dest.channel_id;
+    if !consume_flow(
+        deps.storage,
+        env.block.time,
+        VOLUME_IN,
+        channel_id,
+        &swap.deposit,
+    )? {
+        let err = ContractError::RateLimited {
+            channel_id: channel_id.clone(),
+            denom: denom_key(&swap.deposit.denom),
+        };
+        return Ok(IbcReceiveResponse::new()
+            .add_attribute("method", "ibc_packet_receive")
+            .set_ack(StdAck::fail(err.to_string())));
+    }
+
+    swap.status = SwapStatus::Open;
+    swaps_b().save(deps.storage, id, &swap)?;
     Ok(IbcReceiveResponse::new()
         .add_attribute("method", "ibc_packet_receive")
-        .set_ack(StdAck::success(&id)))
+        .add_event(swap_event(SWAP_LISTED, id, "B", channel_id, &swap, None))
+        .set_ack(StdAck::success(id)))
 }
 
 pub fn accept_side_a(
@@ -116,31 +233,185 @@ pub fn accept_side_a(
     env: Env,
     id: u64,
     sender: String,
-    _msg: IbcPacketReceiveMsg,
+    price: Option<Uint128>,
+    recipient: Option<String>,
+    msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     let state = STATE.load(deps.storage)?;
-    let swap = SWAPS_A.load(deps.storage, id)?;
-    SWAPS_A.remove(deps.storage, id);
-    match swap.deposit.denom {
-        Denom::Native(denom) => {
-            let coin = Coin {
-                denom,
-                amount: swap.deposit.amount,
-            };
-            let transfer_msg = IbcMsg::Transfer {
-                channel_id: swap.deposit_transfer_channel_id,
-                to_address: sender,
-                amount: coin,
-                timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
-            };
+    let mut swap = swaps_a().load(deps.storage, id)?;
+    let channel_id = &msg.packet.dest.channel_id;
+    if swap.local {
+        return Ok(IbcReceiveResponse::new()
+            .add_attribute("method", "ibc_packet_receive")
+            .set_ack(StdAck::fail(ContractError::LocalSwap {}.to_string())));
+    }
 
+    // Side B prices auctions, within the bounds the maker set.
+    if let Some(price) = price {
+        if !swap.accepts_price(price) {
             return Ok(IbcReceiveResponse::new()
                 .add_attribute("method", "ibc_packet_receive")
-                .add_message(transfer_msg)
-                .set_ack(StdAck::success(&id)));
+                .set_ack(StdAck::fail(ContractError::InvalidPrice {}.to_string())));
+        }
+        swap.ask.amount = price;
+    }
+
+    // HTLC deposits stay escrowed until the maker reveals the preimage,
+    // only the taker it will be released to is recorded.
+    if swap.htlc.is_some() {
+        let res = IbcReceiveResponse::new().add_attribute("method", "ibc_packet_receive");
+        if swap.status == SwapStatus::Accepted {
+            return Ok(res.set_ack(StdAck::fail(ContractError::SwapNotOpen {}.to_string())));
+        }
+        swap.status = SwapStatus::Accepted;
+        swap.taker = Some(Addr::unchecked(sender));
+        swap.taker_recipient = recipient.map(Addr::unchecked);
+        swaps_a().save(deps.storage, id, &swap)?;
+        return Ok(res
+            .add_event(swap_event(SWAP_ACCEPTED, id, "A", channel_id, &swap, None))
+            .set_ack(StdAck::success(id)));
+    }
+
+    if !consume_flow(
+        deps.storage,
+        env.block.time,
+        VOLUME_OUT,
+        &swap.deposit_transfer_channel_id,
+        &swap.deposit,
+    )? {
+        let err = ContractError::RateLimited {
+            channel_id: swap.deposit_transfer_channel_id.clone(),
+            denom: denom_key(&swap.deposit.denom),
+        };
+        return Ok(IbcReceiveResponse::new()
+            .add_attribute("method", "ibc_packet_receive")
+            .set_ack(StdAck::fail(err.to_string())));
+    }
+    // Tokenized swaps name the position holder side B pays the ask to.
+    let ack = match position_holder(deps.as_ref(), id)? {
+        Some(holder) => {
+            swap.recipient = Some(holder.clone());
+            StdAck::success(AcceptAck {
+                id,
+                payee: holder.into_string(),
+            })
         }
-        Denom::Cw20(_) => unimplemented!(),
+        None => StdAck::success(id),
+    };
+    swaps_a().remove(deps.storage, id)?;
+    POSITIONS.remove(deps.storage, id);
+    update_stats(deps.storage, |stats| stats.swaps_settled += 1)?;
+    record_volume(deps.storage, VOLUME_OUT, &swap.deposit)?;
+    escrow_out(deps.storage, &swap.deposit)?;
+    let event = swap_event(SWAP_SETTLED, id, "A", channel_id, &swap, Some(&sender));
+    let transfer_msg = transfer_token(
+        deps.storage,
+        &swap.deposit,
+        &swap.deposit_transfer_channel_id,
+        &recipient.unwrap_or(sender),
+        env.block.time.plus_seconds(state.packet_lifetime),
+    )?;
+    let hook = SwapHookMsg::SwapSettled {
+        id,
+        side: "A".to_string(),
+        swap,
     };
+
+    Ok(IbcReceiveResponse::new()
+        .add_attribute("method", "ibc_packet_receive")
+        .add_message(transfer_msg)
+        .add_event(event)
+        .add_submessages(swap_hooks(deps.storage, hook)?)
+        .set_ack(ack))
+}
+
+pub fn refund_side_a(
+    deps: DepsMut,
+    id: u64,
+    msg: IbcPacketReceiveMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let res = IbcReceiveResponse::new().add_attribute("method", "ibc_packet_receive");
+    let swap = swaps_a().load(deps.storage, id)?;
+    if swap.local {
+        return Ok(res.set_ack(StdAck::fail(ContractError::LocalSwap {}.to_string())));
+    }
+    if swap.status == SwapStatus::Accepted {
+        return Ok(res.set_ack(StdAck::fail(ContractError::SwapNotOpen {}.to_string())));
+    }
+
+    let (refund_msg, event, hooks) =
+        refund_deposit(deps, id, &swap, &msg.packet.dest.channel_id, false)?;
+    Ok(res
+        .add_message(refund_msg)
+        .add_event(event)
+        .add_submessages(hooks)
+        .set_ack(StdAck::success(id)))
+}
+
+/// Withdraws a listing its maker cancelled, unless it was accepted or
+/// has a bid to honour.
+fn cancel_side_b(deps: DepsMut, id: u64) -> Result<IbcReceiveResponse, ContractError> {
+    let res = IbcReceiveResponse::new().add_attribute("method", "ibc_packet_receive");
+    let open = match swaps_b().may_load(deps.storage, id)? {
+        Some(swap) => {
+            let bid = swap.english_auction.and_then(|auction| auction.highest_bid);
+            swap.status == SwapStatus::Open && bid.is_none()
+        }
+        None => false,
+    };
+    if !open {
+        return Ok(res.set_ack(StdAck::fail(ContractError::SwapNotOpen {}.to_string())));
+    }
+    swaps_b().remove(deps.storage, id)?;
+    update_stats(deps.storage, |stats| stats.swaps_cancelled += 1)?;
+    Ok(res.set_ack(StdAck::success(id)))
+}
+
+/// Returns the deposit of a side A swap that side B refused or
+/// withdrew to its maker, or the holder of its position. `cancelled`
+/// counts it as a cancellation by its owner rather than a refund.
+pub fn refund_deposit(
+    deps: DepsMut,
+    id: u64,
+    swap: &Swap,
+    channel_id: &str,
+    cancelled: bool,
+) -> Result<(CosmosMsg, Event, Vec<SubMsg>), ContractError> {
+    let payee = position_holder(deps.as_ref(), id)?.unwrap_or_else(|| swap.refund_payee().clone());
+    swaps_a().remove(deps.storage, id)?;
+    POSITIONS.remove(deps.storage, id);
+    update_stats(deps.storage, |stats| match cancelled {
+        true => stats.swaps_cancelled += 1,
+        false => stats.swaps_refunded += 1,
+    })?;
+    record_volume(deps.storage, VOLUME_REFUNDED, &swap.deposit)?;
+    escrow_out(deps.storage, &swap.deposit)?;
+    let event = swap_event(SWAP_REFUNDED, id, "A", channel_id, swap, None);
+    let refund_msg = send_token(&swap.deposit, payee.as_str())?;
+    let hook = SwapHookMsg::SwapCancelled {
+        id,
+        side: "A".to_string(),
+        swap: swap.clone(),
+    };
+    Ok((refund_msg, event, swap_hooks(deps.storage, hook)?))
+}
+
+/// Side B relayed the preimage it was paid with, release the deposit.
+pub fn reveal_side_a(
+    deps: DepsMut,
+    env: Env,
+    id: u64,
+    preimage: HexBinary,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let res = IbcReceiveResponse::new().add_attribute("method", "ibc_packet_receive");
+    match release_deposit(deps, &env, id, &preimage) {
+        Ok((transfer_msg, event, hooks)) => Ok(res
+            .add_message(transfer_msg)
+            .add_event(event)
+            .add_submessages(hooks)
+            .set_ack(StdAck::success(id))),
+        Err(err) => Ok(res.set_ack(StdAck::fail(err.to_string()))),
+    }
 }
 
 #[entry_point]
@@ -150,46 +421,196 @@ pub fn ibc_packet_ack(
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     // we need to parse the ack based on our request
-    let original_packet: PacketMsg = from_slice(&msg.original_packet.data)?;
+    let version = STATE.load(deps.storage)?.ibc_version;
+    let original_packet = decode_packet(version.as_deref(), &msg.original_packet.data)?;
+    let channel_id = &msg.original_packet.src.channel_id;
 
     match original_packet {
-        PacketMsg::AcceptSideA { id, sender: _ } => {
-            let swap = SWAPS_B.load(deps.storage, id)?;
-            SWAPS_B.remove(deps.storage, id);
-            let state = STATE.load(deps.storage)?;
-            match swap.ask.denom {
-                Denom::Native(denom) => {
-                    let coin = Coin {
-                        denom,
-                        amount: swap.ask.amount,
-                    };
-                    let transfer_msg = IbcMsg::Transfer {
-                        channel_id: swap.ask_transfer_channel_id,
-                        to_address: swap.deposit_address.to_string(),
-                        amount: coin,
-                        timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
-                    };
-
-                    return Ok(IbcBasicResponse::new()
-                        .add_attribute("method", "ibc_packet_ack")
-                        .add_message(transfer_msg));
-                }
-                Denom::Cw20(_) => unimplemented!(),
+        PacketMsg::AcceptSideA { id, .. } => {
+            let res = IbcBasicResponse::new().add_attribute("method", "ibc_packet_ack");
+            if try_get_ack_error(&msg.acknowledgement).is_some() {
+                return reopen_swap(deps, &env, id, channel_id, res);
+            }
+            // HTLC payments are released by the maker's preimage instead,
+            // which may even have happened before this ack.
+            let mut swap = match swaps_b().may_load(deps.storage, id)? {
+                Some(swap) if swap.htlc.is_none() => swap,
+                _ => return Ok(res),
             };
+            swaps_b().remove(deps.storage, id)?;
+            // Tokenized swaps pay whoever held the position on side A.
+            if let Some(payee) = try_get_ack_payee(&msg.acknowledgement) {
+                swap.recipient = Some(Addr::unchecked(payee));
+            }
+            // Over the rate limit the payout waits for `ReleasePayout`.
+            if !consume_flow(
+                deps.storage,
+                env.block.time,
+                VOLUME_OUT,
+                &swap.ask_transfer_channel_id,
+                &swap.ask,
+            )? {
+                let payout = Payout {
+                    channel_id: channel_id.to_string(),
+                    swap,
+                };
+                PAYOUTS.save(deps.storage, id, &payout)?;
+                return Ok(res.add_attribute("payout", "queued"));
+            }
+            let (transfer_msg, event, hooks) = pay_maker(deps, &env, id, &swap, channel_id)?;
+
+            Ok(res
+                .add_message(transfer_msg)
+                .add_event(event)
+                .add_submessages(hooks))
         }
-        PacketMsg::CreateSideB { id: _, swap: _ } => {
-            return Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_ack"))
+        PacketMsg::CreateSideB { id, swap: _ } => {
+            let mut res = IbcBasicResponse::new().add_attribute("method", "ibc_packet_ack");
+            // the listing is only open once side B has stored it.
+            // It may already be settled if the accept raced the ack.
+            let mut swap = match swaps_a().may_load(deps.storage, id)? {
+                Some(swap) => swap,
+                None => return Ok(res),
+            };
+            if try_get_ack_error(&msg.acknowledgement).is_some() {
+                // Side B refused the listing, return the deposit.
+                let (refund_msg, event, hooks) =
+                    refund_deposit(deps, id, &swap, channel_id, false)?;
+                return Ok(res
+                    .add_message(refund_msg)
+                    .add_event(event)
+                    .add_submessages(hooks));
+            }
+            if swap.status == SwapStatus::Pending {
+                swap.status = SwapStatus::Open;
+                swaps_a().save(deps.storage, id, &swap)?;
+            }
+            res = res.add_event(swap_event(SWAP_LISTED, id, "A", channel_id, &swap, None));
+            Ok(res)
         }
+        // Side B withdrew the listing, so nothing can settle it anymore.
+        PacketMsg::CancelSideB { id } => {
+            let res = IbcBasicResponse::new().add_attribute("method", "ibc_packet_ack");
+            if try_get_ack_error(&msg.acknowledgement).is_some() {
+                return Ok(res);
+            }
+            match swaps_a().may_load(deps.storage, id)? {
+                Some(swap) => {
+                    let (refund_msg, event, hooks) =
+                        refund_deposit(deps, id, &swap, channel_id, true)?;
+                    Ok(res
+                        .add_message(refund_msg)
+                        .add_event(event)
+                        .add_submessages(hooks))
+                }
+                None => Ok(res),
+            }
+        }
+        // The deposit is released on receipt, or claimed by the taker
+        // directly should that fail.
+        PacketMsg::RevealSideA { .. }
+        | PacketMsg::RefundSideA { .. }
+        | PacketMsg::RequestQuote { .. }
+        | PacketMsg::CloseRequest { .. } => {
+            Ok(IbcBasicResponse::new().add_attribute("method", "ibc_packet_ack"))
+        }
+    }
+}
+
+/// Side A never settled a swap accepted on side B, so the taker's
+/// payment is refunded and the swap reopened. An auction's price can
+/// not be replayed, so auctions are withdrawn instead and side A
+/// returns the deposit.
+fn reopen_swap(
+    deps: DepsMut,
+    env: &Env,
+    id: u64,
+    channel_id: &str,
+    res: IbcBasicResponse,
+) -> Result<IbcBasicResponse, ContractError> {
+    let mut swap = match swaps_b().may_load(deps.storage, id)? {
+        Some(swap) if swap.status == SwapStatus::Accepted => swap,
+        _ => return Ok(res),
+    };
+    let taker = swap.taker.take().unwrap();
+    swap.taker_recipient = None;
+    record_volume(deps.storage, VOLUME_REFUNDED, &swap.ask)?;
+    escrow_out(deps.storage, &swap.ask)?;
+    let event = swap_event(
+        SWAP_REFUNDED,
+        id,
+        "B",
+        channel_id,
+        &swap,
+        Some(taker.as_str()),
+    );
+    let res = res
+        .add_message(send_token(&swap.ask, taker.as_str())?)
+        .add_event(event);
+
+    if swap.dutch_auction.is_none() && swap.english_auction.is_none() {
+        swap.status = SwapStatus::Open;
+        swaps_b().save(deps.storage, id, &swap)?;
+        return Ok(res);
     }
+
+    swaps_b().remove(deps.storage, id)?;
+    let state = STATE.load(deps.storage)?;
+    let packet_msg = IbcMsg::SendPacket {
+        channel_id: channel_id.to_string(),
+        data: encode_packet(state.ibc_version.as_deref(), &PacketMsg::RefundSideA { id })?,
+        timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
+    };
+    Ok(res.add_message(packet_msg))
 }
 
+/// Rolls back each timed out packet on its own, so the swaps of a
+/// batch succeed or fail independently.
 #[entry_point]
 pub fn ibc_packet_timeout(
-    _deps: DepsMut,
-    _env: Env,
-    _msg: IbcPacketTimeoutMsg,
-) -> StdResult<IbcBasicResponse> {
-    //TODO: return funds and roll back state.
+    deps: DepsMut,
+    env: Env,
+    msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let version = STATE.load(deps.storage)?.ibc_version;
+    let packet = decode_packet(version.as_deref(), &msg.packet.data)?;
+    let channel_id = &msg.packet.src.channel_id;
+    let res = IbcBasicResponse::new().add_attribute("method", "ibc_packet_timeout");
 
-    Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_timeout"))
+    match packet {
+        // Side B never listed the swap, return the deposit.
+        PacketMsg::CreateSideB { id, .. } => match swaps_a().may_load(deps.storage, id)? {
+            Some(swap) if swap.status == SwapStatus::Pending => {
+                let (refund_msg, event, hooks) =
+                    refund_deposit(deps, id, &swap, channel_id, false)?;
+                Ok(res
+                    .add_message(refund_msg)
+                    .add_event(event)
+                    .add_submessages(hooks))
+            }
+            _ => Ok(res),
+        },
+        PacketMsg::AcceptSideA { id, .. } => reopen_swap(deps, &env, id, channel_id, res),
+        // Side A still holds the deposit of the withdrawn listing.
+        PacketMsg::RefundSideA { id } => {
+            let state = STATE.load(deps.storage)?;
+            let packet_msg = IbcMsg::SendPacket {
+                channel_id: channel_id.to_string(),
+                data: encode_packet(state.ibc_version.as_deref(), &PacketMsg::RefundSideA { id })?,
+                timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
+            };
+            Ok(res.add_message(packet_msg))
+        }
+        // Side A never saw the request, so no quote can answer it.
+        PacketMsg::RequestQuote { id, .. } => {
+            RFQS_B.remove(deps.storage, id);
+            Ok(res)
+        }
+        // The taker can still claim with the preimage revealed here,
+        // and quotes for a closed request are refused on listing.
+        PacketMsg::RevealSideA { .. } | PacketMsg::CloseRequest { .. } => Ok(res),
+        // The listing is still open on side B, the swap may be
+        // cancelled again.
+        PacketMsg::CancelSideB { .. } => Ok(res),
+    }
 }
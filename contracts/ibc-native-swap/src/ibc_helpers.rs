@@ -1,10 +1,17 @@
+use std::convert::TryFrom;
+
 use cosmwasm_std::{
     from_binary, from_slice, to_binary, Binary, IbcAcknowledgement, IbcChannel, IbcEndpoint,
     IbcOrder,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{ibc::IBC_VERSION, ContractError};
+use crate::ibc::{
+    IBC_VERSION, IBC_VERSION_PREFIX, LEGACY_IBC_REVISION, MAX_IBC_REVISION, MIN_IBC_REVISION,
+};
+use crate::migrations::v0_1::OldSwap;
+use crate::msg::{AcceptAck, PacketEnvelope, PacketMsg};
+use crate::ContractError;
 
 /// Tries to remove the source prefix from a given class_id. If the
 /// class_id does not begin with the given prefix, returns
@@ -98,49 +105,162 @@ pub fn try_get_ack_error(ack: &IbcAcknowledgement) -> Option<String> {
     }
 }
 
-/// Validates order and version information for ics721. We expect
-/// ics721-1 as the version and an unordered channel.
-pub(crate) fn validate_order_and_version(
-    channel: &IbcChannel,
-    counterparty_version: Option<&str>,
-) -> Result<(), ContractError> {
+/// Gets the position holder from the acknowledgement of a tokenized
+/// swap's accept, if any.
+pub fn try_get_ack_payee(ack: &IbcAcknowledgement) -> Option<String> {
+    match from_binary(&ack.data).ok()? {
+        StdAck::Result(data) => from_binary::<AcceptAck>(&data).ok().map(|ack| ack.payee),
+        StdAck::Error(_) => None,
+    }
+}
+
+/// Parses the protocol revision out of a channel version string. For
+/// example, `native-swap-2` parses to `Some(2)`. Returns `None` for
+/// anything that is not one of our versions.
+pub(crate) fn parse_version(version: &str) -> Option<u32> {
+    version.strip_prefix(IBC_VERSION_PREFIX)?.parse().ok()
+}
+
+/// Errors unless `version` names a protocol revision this contract
+/// can speak.
+pub(crate) fn validate_version(version: &str) -> Result<u32, ContractError> {
+    match parse_version(version) {
+        Some(revision) if (MIN_IBC_REVISION..=MAX_IBC_REVISION).contains(&revision) => Ok(revision),
+        _ => Err(ContractError::InvalidVersion {
+            actual: version.to_string(),
+            expected: format!(
+                "{}{}..={}{}",
+                IBC_VERSION_PREFIX, MIN_IBC_REVISION, IBC_VERSION_PREFIX, MAX_IBC_REVISION
+            ),
+        }),
+    }
+}
+
+/// Validates the channel ordering. We expect an unordered channel.
+pub(crate) fn validate_order(channel: &IbcChannel) -> Result<(), ContractError> {
     // We expect an unordered channel here. Ordered channels have the
     // property that if a message is lost the entire channel will stop
     // working until you start it again.
     if channel.order != IbcOrder::Unordered {
         return Err(ContractError::OrderedChannel {});
     }
+    Ok(())
+}
+
+/// Picks the version to answer a channel handshake with.
+///
+/// During `OpenInit` we propose our highest revision, unless the
+/// relayer pinned a specific one that we support. During `OpenTry`
+/// the counterparty has proposed its highest revision, so we answer
+/// with the highest revision that both sides speak. The initiating
+/// side accepts that answer in `OpenAck`.
+pub(crate) fn negotiate_version(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<String, ContractError> {
+    validate_order(channel)?;
 
-    if channel.version != IBC_VERSION {
-        return Err(ContractError::InvalidVersion {
-            actual: channel.version.to_string(),
-            expected: IBC_VERSION.to_string(),
-        });
-    }
-
-    // Make sure that we're talking with a counterparty who speaks the
-    // same "protocol" as us.
-    //
-    // For a connection between chain A and chain B being established
-    // by chain A, chain B knows counterparty information during
-    // `OpenTry` and chain A knows counterparty information during
-    // `OpenAck`. We verify it when we have it but when we don't it's
-    // alright.
-    if let Some(counterparty_version) = counterparty_version {
-        if counterparty_version != IBC_VERSION {
-            return Err(ContractError::InvalidVersion {
-                actual: counterparty_version.to_string(),
-                expected: IBC_VERSION.to_string(),
-            });
+    match counterparty_version {
+        Some(counterparty_version) => {
+            let proposed = parse_version(counterparty_version).ok_or_else(|| {
+                ContractError::InvalidVersion {
+                    actual: counterparty_version.to_string(),
+                    expected: IBC_VERSION.to_string(),
+                }
+            })?;
+            let revision = proposed.min(MAX_IBC_REVISION);
+            let version = format!("{}{}", IBC_VERSION_PREFIX, revision);
+            validate_version(&version)?;
+            Ok(version)
+        }
+        None if channel.version.is_empty() => Ok(IBC_VERSION.to_string()),
+        None => {
+            validate_version(&channel.version)?;
+            Ok(channel.version.clone())
         }
     }
+}
 
-    Ok(())
+/// Validates order and version information once the handshake
+/// completes and returns the version the channel settled on.
+///
+/// During `OpenAck` the counterparty version is the one chosen by the
+/// other side in `OpenTry`, which may be lower than what we proposed,
+/// so it takes precedence over our own channel version.
+pub(crate) fn validate_order_and_version(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<String, ContractError> {
+    validate_order(channel)?;
+
+    let version = counterparty_version.unwrap_or(&channel.version);
+    validate_version(version)?;
+
+    Ok(version.to_string())
+}
+
+/// Whether a channel on `version` speaks the legacy revision, whose
+/// peers only know 0.1.x packets and swaps.
+pub(crate) fn is_legacy(version: Option<&str>) -> bool {
+    !matches!(version.and_then(parse_version), Some(revision) if revision > LEGACY_IBC_REVISION)
+}
+
+/// `PacketMsg::CreateSideB` as 0.1.x releases decode it. They deny
+/// unknown fields, so only swaps without newer terms can be listed.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LegacyPacketMsg {
+    CreateSideB { id: u64, swap: OldSwap },
+}
+
+/// Serializes a packet in the format understood by the channel's
+/// negotiated version. Legacy `native-swap-1` channels receive a bare
+/// `PacketMsg`, everything newer receives a `PacketEnvelope`.
+pub(crate) fn encode_packet(
+    version: Option<&str>,
+    msg: &PacketMsg,
+) -> Result<Binary, ContractError> {
+    let data = match msg {
+        _ if !is_legacy(version) => to_binary(&PacketEnvelope {
+            version: IBC_VERSION.to_string(),
+            msg: msg.clone(),
+        })?,
+        PacketMsg::CreateSideB { id, swap } => to_binary(&LegacyPacketMsg::CreateSideB {
+            id: *id,
+            swap: OldSwap::try_from(swap)?,
+        })?,
+        _ => to_binary(msg)?,
+    };
+    Ok(data)
+}
+
+/// Deserializes a packet sent in either the enveloped or the legacy
+/// bare format, so that both sides keep working while one of them is
+/// being upgraded. Envelopes must carry the channel's negotiated
+/// `version`.
+pub(crate) fn decode_packet(
+    version: Option<&str>,
+    data: &Binary,
+) -> Result<PacketMsg, ContractError> {
+    match from_slice::<PacketEnvelope>(data) {
+        Ok(envelope) if version == Some(envelope.version.as_str()) => Ok(envelope.msg),
+        Ok(envelope) => Err(ContractError::InvalidVersion {
+            actual: envelope.version,
+            expected: version.unwrap_or_default().to_string(),
+        }),
+        Err(_) => Ok(from_slice::<PacketMsg>(data)?),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::testing::mock_ibc_channel;
+    use cosmwasm_std::{Addr, Uint128};
+    use cw20::Denom;
+
     use super::*;
+    use crate::state::{Swap, SwapStatus, Token};
 
     #[test]
     fn test_pop_source_simple() {
@@ -194,4 +314,144 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_negotiate_version() {
+        let channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, "");
+
+        // OpenInit with no pinned version proposes our highest.
+        assert_eq!(negotiate_version(&channel, None).unwrap(), IBC_VERSION);
+
+        // OpenTry answers with the highest revision both sides speak.
+        assert_eq!(
+            negotiate_version(&channel, Some("native-swap-1")).unwrap(),
+            "native-swap-1"
+        );
+        assert_eq!(
+            negotiate_version(&channel, Some("native-swap-9")).unwrap(),
+            IBC_VERSION
+        );
+
+        negotiate_version(&channel, Some("native-swap-0")).unwrap_err();
+        negotiate_version(&channel, Some("ics721-1")).unwrap_err();
+
+        let ordered = mock_ibc_channel("channel-0", IbcOrder::Ordered, IBC_VERSION);
+        negotiate_version(&ordered, None).unwrap_err();
+    }
+
+    #[test]
+    fn test_validate_order_and_version_prefers_counterparty() {
+        let channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, IBC_VERSION);
+        assert_eq!(
+            validate_order_and_version(&channel, Some("native-swap-1")).unwrap(),
+            "native-swap-1"
+        );
+        assert_eq!(
+            validate_order_and_version(&channel, None).unwrap(),
+            IBC_VERSION
+        );
+    }
+
+    #[test]
+    fn test_packet_encoding_by_version() {
+        let msg = PacketMsg::AcceptSideA {
+            id: 1,
+            sender: "taker".to_string(),
+            price: None,
+            recipient: None,
+        };
+
+        let legacy = encode_packet(Some("native-swap-1"), &msg).unwrap();
+        assert_eq!(from_slice::<PacketMsg>(&legacy).unwrap(), msg);
+        assert_eq!(decode_packet(Some("native-swap-1"), &legacy).unwrap(), msg);
+
+        let enveloped = encode_packet(Some(IBC_VERSION), &msg).unwrap();
+        let envelope: PacketEnvelope = from_slice(&enveloped).unwrap();
+        assert_eq!(envelope.version, IBC_VERSION);
+        assert_eq!(decode_packet(Some(IBC_VERSION), &enveloped).unwrap(), msg);
+        // Envelopes must match the version negotiated on the channel.
+        decode_packet(Some("native-swap-1"), &enveloped).unwrap_err();
+        decode_packet(None, &enveloped).unwrap_err();
+
+        let unknown = to_binary(&PacketEnvelope {
+            version: "native-swap-9".to_string(),
+            msg,
+        })
+        .unwrap();
+        decode_packet(Some(IBC_VERSION), &unknown).unwrap_err();
+    }
+
+    #[test]
+    fn test_legacy_listing_encoding() {
+        // As 0.1.x decodes it, with unknown fields denied.
+        #[cw_serde]
+        enum OldPacketMsg {
+            CreateSideB { id: u64, swap: OldSwap },
+        }
+
+        let old_swap = OldSwap {
+            deposit: Token {
+                denom: Denom::Native("ujuno".to_string()),
+                amount: Uint128::new(100),
+                token_id: None,
+            },
+            deposit_address: Addr::unchecked("maker"),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask: Token {
+                denom: Denom::Native("uosmo".to_string()),
+                amount: Uint128::new(200),
+                token_id: None,
+            },
+            ask_address: None,
+            ask_transfer_channel_id: "channel-2".to_string(),
+        };
+        let swap = Swap {
+            status: SwapStatus::Pending,
+            ..old_swap.clone().into()
+        };
+        let msg = PacketMsg::CreateSideB { id: 1, swap };
+
+        let legacy = encode_packet(Some("native-swap-1"), &msg).unwrap();
+        assert_eq!(
+            from_slice::<OldPacketMsg>(&legacy).unwrap(),
+            OldPacketMsg::CreateSideB {
+                id: 1,
+                swap: old_swap.clone()
+            }
+        );
+        assert_eq!(
+            decode_packet(Some("native-swap-1"), &legacy).unwrap(),
+            PacketMsg::CreateSideB {
+                id: 1,
+                swap: old_swap.clone().into()
+            }
+        );
+
+        // Terms the peer does not know are refused, not dropped.
+        let swap = Swap {
+            recipient: Some(Addr::unchecked("recipient")),
+            ..old_swap.clone().into()
+        };
+        let err = encode_packet(
+            Some("native-swap-1"),
+            &PacketMsg::CreateSideB { id: 1, swap },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::LegacyChannel {}));
+        let swap = Swap {
+            deposit: Token::nft("nft", "1".to_string()),
+            ..old_swap.clone().into()
+        };
+        let err = encode_packet(
+            Some("native-swap-1"),
+            &PacketMsg::CreateSideB { id: 1, swap },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::LegacyChannel {}));
+        let swap = Swap {
+            recipient: Some(Addr::unchecked("recipient")),
+            ..old_swap.into()
+        };
+        encode_packet(Some(IBC_VERSION), &PacketMsg::CreateSideB { id: 1, swap }).unwrap();
+    }
 }
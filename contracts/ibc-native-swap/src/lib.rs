@@ -1,7 +1,9 @@
 pub mod contract;
 mod error;
+pub mod events;
 pub mod ibc;
 pub mod ibc_helpers;
+pub mod migrations;
 pub mod msg;
 pub mod state;
 
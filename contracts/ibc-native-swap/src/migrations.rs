@@ -0,0 +1,260 @@
+use cosmwasm_std::{Order, StdResult, Storage};
+use semver::Version;
+
+use crate::ibc::{IBC_VERSION_PREFIX, LEGACY_IBC_REVISION};
+use crate::state::{
+    escrow_in, swaps_a, swaps_b, State, Swap, SwapStatus, Token, ESCROW, PAYOUTS, STATE,
+};
+
+/// First release with a `migrate` entry point. Channels opened before
+/// it always spoke the legacy protocol revision.
+pub const V0_2_0: Version = Version::new(0, 2, 0);
+
+/// Runs every storage migration between the stored contract version
+/// and the current one, oldest first, then rebuilds the escrow ledger.
+pub fn migrate_storage(storage: &mut dyn Storage, from: &Version) -> StdResult<()> {
+    if *from < V0_2_0 {
+        v0_1::migrate_state(storage)?;
+        v0_1::migrate_swaps(storage)?;
+    }
+    rebuild_escrow(storage)
+}
+
+/// Recomputes what the contract owes from the swaps and payouts it
+/// holds, for swaps escrowed before the ledger was kept.
+fn rebuild_escrow(storage: &mut dyn Storage) -> StdResult<()> {
+    let denoms = ESCROW
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for denom in denoms {
+        ESCROW.remove(storage, &denom);
+    }
+
+    // Side A holds every deposit until it is released or refunded.
+    let mut held = swaps_a()
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, swap)| swap.deposit))
+        .collect::<StdResult<Vec<_>>>()?;
+    // Side B holds accepted payments, highest bids and queued payouts.
+    for item in swaps_b().range(storage, None, None, Order::Ascending) {
+        let (_, swap) = item?;
+        let bid = swap
+            .english_auction
+            .as_ref()
+            .and_then(|auction| auction.highest_bid.as_ref());
+        match (&swap.status, bid) {
+            (SwapStatus::Accepted, _) => held.push(swap.ask),
+            (_, Some(bid)) => held.push(Token {
+                amount: bid.amount,
+                ..swap.ask
+            }),
+            _ => {}
+        }
+    }
+    for item in PAYOUTS.range(storage, None, None, Order::Ascending) {
+        held.push(item?.1.swap.ask);
+    }
+
+    for token in &held {
+        escrow_in(storage, token)?;
+    }
+    Ok(())
+}
+
+/// Storage layout written by 0.1.x releases, which is also how
+/// listings are sent to peers on the legacy protocol revision.
+pub(crate) mod v0_1 {
+    use std::convert::TryFrom;
+
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, IbcEndpoint};
+    use cw_storage_plus::{Item, Map};
+
+    use super::*;
+    use crate::ContractError;
+
+    #[cw_serde]
+    pub struct OldState {
+        pub owner: Addr,
+        pub packet_lifetime: u64,
+        pub endpoint: Option<IbcEndpoint>,
+        pub counterparty_endpoint: Option<IbcEndpoint>,
+    }
+
+    #[cw_serde]
+    pub struct OldSwap {
+        pub deposit: Token,
+        pub deposit_address: Addr,
+        pub deposit_transfer_channel_id: String,
+        pub ask: Token,
+        pub ask_address: Option<Addr>,
+        pub ask_transfer_channel_id: String,
+    }
+
+    /// Fails for swaps with terms added since 0.1.x, which a legacy
+    /// peer would drop and list the swap without.
+    impl TryFrom<&Swap> for OldSwap {
+        type Error = ContractError;
+
+        fn try_from(swap: &Swap) -> Result<Self, Self::Error> {
+            let plain = swap.htlc.is_none()
+                && swap.dutch_auction.is_none()
+                && swap.english_auction.is_none()
+                && swap.recipient.is_none()
+                && swap.refund_address.is_none()
+                && swap.allowed_takers.is_none()
+                && swap.rfq_id.is_none()
+                && !swap.deposit.is_nft()
+                && !swap.ask.is_nft();
+            if !plain {
+                return Err(ContractError::LegacyChannel {});
+            }
+            Ok(OldSwap {
+                deposit: swap.deposit.clone(),
+                deposit_address: swap.deposit_address.clone(),
+                deposit_transfer_channel_id: swap.deposit_transfer_channel_id.clone(),
+                ask: swap.ask.clone(),
+                ask_address: swap.ask_address.clone(),
+                ask_transfer_channel_id: swap.ask_transfer_channel_id.clone(),
+            })
+        }
+    }
+
+    impl From<OldSwap> for Swap {
+        fn from(old: OldSwap) -> Self {
+            Swap {
+                deposit: old.deposit,
+                deposit_address: old.deposit_address,
+                deposit_transfer_channel_id: old.deposit_transfer_channel_id,
+                ask: old.ask,
+                ask_address: old.ask_address,
+                ask_transfer_channel_id: old.ask_transfer_channel_id,
+                status: SwapStatus::Open,
+                htlc: None,
+                taker: None,
+                dutch_auction: None,
+                english_auction: None,
+                rfq_id: None,
+                recipient: None,
+                taker_recipient: None,
+                refund_address: None,
+                allowed_takers: None,
+                local: false,
+            }
+        }
+    }
+
+    const OLD_STATE: Item<OldState> = Item::new("state");
+    const OLD_SWAPS_A: Map<u64, OldSwap> = Map::new("swaps_a");
+    const OLD_SWAPS_B: Map<u64, OldSwap> = Map::new("swaps_b");
+
+    /// Records the legacy protocol revision for an already bound
+    /// channel, since 0.1.x did not negotiate one.
+    pub fn migrate_state(storage: &mut dyn Storage) -> StdResult<()> {
+        let old = OLD_STATE.load(storage)?;
+        let ibc_version = old
+            .endpoint
+            .as_ref()
+            .map(|_| format!("{}{}", IBC_VERSION_PREFIX, LEGACY_IBC_REVISION));
+        STATE.save(
+            storage,
+            &State {
+                owner: old.owner,
+                packet_lifetime: old.packet_lifetime,
+                endpoint: old.endpoint,
+                counterparty_endpoint: old.counterparty_endpoint,
+                ibc_version,
+            },
+        )
+    }
+
+    pub fn migrate_swaps(storage: &mut dyn Storage) -> StdResult<()> {
+        let old_swaps_a = OLD_SWAPS_A
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, swap) in old_swaps_a {
+            swaps_a().save(storage, id, &swap.into())?;
+        }
+
+        let old_swaps_b = OLD_SWAPS_B
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, swap) in old_swaps_b {
+            swaps_b().save(storage, id, &swap.into())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{to_vec, Addr, IbcEndpoint, Uint128};
+    use cw20::Denom;
+
+    use super::v0_1::{OldState, OldSwap};
+    use super::*;
+    use crate::contract::migrate;
+    use crate::msg::MigrateMsg;
+    use crate::ContractError;
+
+    #[test]
+    fn test_migrate_from_v0_1() {
+        let mut deps = mock_dependencies();
+        cw2::set_contract_version(&mut deps.storage, "crates.io:ibc-native-swap", "0.1.0").unwrap();
+        let endpoint = IbcEndpoint {
+            port_id: "wasm.contract".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let old_state = OldState {
+            owner: Addr::unchecked("owner"),
+            packet_lifetime: 60,
+            endpoint: Some(endpoint.clone()),
+            counterparty_endpoint: Some(endpoint),
+        };
+        deps.storage.set(b"state", &to_vec(&old_state).unwrap());
+        let old_swap = OldSwap {
+            deposit: Token {
+                denom: Denom::Native("ujuno".to_string()),
+                amount: Uint128::new(100),
+                token_id: None,
+            },
+            deposit_address: Addr::unchecked("maker"),
+            deposit_transfer_channel_id: "channel-1".to_string(),
+            ask: Token {
+                denom: Denom::Native("uosmo".to_string()),
+                amount: Uint128::new(200),
+                token_id: None,
+            },
+            ask_address: None,
+            ask_transfer_channel_id: "channel-2".to_string(),
+        };
+        cw_storage_plus::Map::<u64, OldSwap>::new("swaps_a")
+            .save(&mut deps.storage, 0, &old_swap)
+            .unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.ibc_version.as_deref(), Some("native-swap-1"));
+        assert_eq!(
+            swaps_a().load(&deps.storage, 0).unwrap().deposit_address,
+            "maker"
+        );
+        assert_eq!(
+            ESCROW.load(&deps.storage, "ujuno").unwrap(),
+            Uint128::new(100)
+        );
+        let version = cw2::get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+        // A second run is a no-op, but downgrades and foreign contracts are refused.
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        cw2::set_contract_version(&mut deps.storage, "crates.io:ibc-native-swap", "9.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrateVersion { .. }));
+        cw2::set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrate { .. }));
+    }
+}
@@ -1,33 +1,592 @@
-use crate::state::{Swap, Token};
+use crate::state::{
+    DenomRule, PriceOracle, RateLimit, Rfq, State, Stats, Swap, SwapStatus, TakerAllowlist, Token,
+};
 use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Addr, Coin, Decimal, HexBinary, IbcTimeout, Uint128};
+use cw20::Denom;
+use cw721::Cw721ReceiveMsg;
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub packet_lifetime: u64,
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     CreateSwap {
         ask: Token,
         deposit_transfer_channel_id: String,
         ask_transfer_channel_id: String,
+        /// Locks the swap behind a hash and time lock instead of
+        /// trusting the counterparty contract to settle it.
+        htlc: Option<HtlcMsg>,
+        /// Lowers the ask linearly from `ask` to a floor.
+        dutch_auction: Option<DutchAuctionMsg>,
+        /// Auctions the deposit on side B, with `ask` as reserve price.
+        english_auction: Option<EnglishAuctionMsg>,
+        /// Receives the ask instead of the maker, on the maker's chain.
+        recipient: Option<String>,
+        /// Receives refunds of the deposit instead of the maker. Set by
+        /// makers that can not hold funds themselves, like the sender
+        /// ibc-hooks derives for a transfer memo. They must name a
+        /// `recipient`, which may then live on any chain.
+        refund_address: Option<String>,
+        /// Only lets these takers accept the swap, for makers that may
+        /// only trade with onboarded counterparties.
+        #[serde(default)]
+        allowed_takers: Option<TakerAllowlist>,
+        /// Lists the swap for takers on this chain only, settled with
+        /// bank sends. Its transfer channels are unused.
+        #[serde(default)]
+        local: bool,
     },
     AcceptSwap {
         id: u64,
+        /// Receives the deposit instead of the taker, on the taker's chain.
+        recipient: Option<String>,
+        /// Accepts a local swap listed on side A of this chain.
+        #[serde(default)]
+        local: bool,
+    },
+    /// Creates several swaps at once. The funds sent must add up to
+    /// their deposits exactly.
+    BatchCreateSwaps { swaps: Vec<SwapOffer> },
+    /// Side B: accepts several swaps at once, paying each its current
+    /// ask out of the funds sent. Whatever is left over is returned.
+    BatchAcceptSwaps {
+        ids: Vec<u64>,
+        recipient: Option<String>,
+    },
+    /// Side B: accepts a swap paid in any token, which the swap router
+    /// exchanges for the ask along `route`. At most `max_input` of the
+    /// funds sent is swapped, and leftovers of either denom are returned.
+    AcceptSwapWithRoute {
+        id: u64,
+        route: Vec<SwapAmountInRoute>,
+        max_input: Uint128,
+    },
+    /// Side B: escrows a bid on an auction swap. It must exceed the
+    /// highest bid, which is refunded.
+    PlaceBid { id: u64 },
+    /// Side B: closes an auction past its deadline. The winning bid is
+    /// settled like an accepted swap, and without bids the deposit is
+    /// returned to the maker.
+    FinalizeAuction { id: u64 },
+    /// Side B: asks makers on side A to quote `want` for a payment in
+    /// `pay_denom`.
+    RequestQuote { want: Token, pay_denom: Denom },
+    /// Side A: answers a request for quote by escrowing exactly what
+    /// it wants, in exchange for `price` of its payment denom.
+    SubmitQuote {
+        rfq_id: u64,
+        price: Uint128,
+        deposit_transfer_channel_id: String,
+        ask_transfer_channel_id: String,
+    },
+    /// Side B: the requester accepts one quote like a swap. The request
+    /// is closed and the other quotes are refunded.
+    AcceptQuote { id: u64 },
+    /// Side B: the requester withdraws a request, refunding its quotes.
+    CancelRequest { rfq_id: u64 },
+    /// Side B: releases the taker's payment of an accepted HTLC swap to
+    /// the maker. The preimage is relayed to side A, which releases the
+    /// deposit to the taker.
+    RevealPreimage { id: u64, preimage: HexBinary },
+    /// Side A: releases the deposit of an accepted HTLC swap to the
+    /// taker with the preimage revealed on side B, for when the relayed
+    /// preimage never arrived.
+    ClaimSwap { id: u64, preimage: HexBinary },
+    /// Owner: sets the rule for a denom, or clears it with `None`.
+    SetDenomRule {
+        denom: String,
+        rule: Option<DenomRule>,
+    },
+    /// Owner: when enabled, only denoms with a rule that does not deny
+    /// them may be swapped.
+    SetDenomAllowlist { enabled: bool },
+    /// Owner: lets a channel to `counterparty_port_id` over
+    /// `connection_id` be opened and bound.
+    AuthorizeChannel {
+        connection_id: String,
+        counterparty_port_id: String,
+    },
+    /// Owner: withdraws a channel authorization. A channel already
+    /// bound stays bound.
+    RevokeChannel {
+        connection_id: String,
+        counterparty_port_id: String,
+    },
+    /// Owner: sets the swap router used by `AcceptSwapWithRoute`.
+    SetSwapRouter { address: String },
+    /// Creates or accepts a swap with the NFT sent, as directed by the
+    /// `ReceiveNftMsg` it carries.
+    ReceiveNft(Cw721ReceiveMsg),
+    /// Owner: sets the ICS-721 bridge NFTs are sent across chains with.
+    SetNftBridge { address: String },
+    /// Owner: allows NFTs of the cw721 contract at `address` to be
+    /// swapped.
+    AllowNftCollection { address: String },
+    /// Owner: stops accepting NFTs of a collection. Swaps already
+    /// created are unaffected.
+    DisallowNftCollection { address: String },
+    /// Side A: mints the maker an NFT of the position `PositionNft`,
+    /// whose holder is paid the ask and may cancel the swap.
+    TokenizeSwap { id: u64 },
+    /// Side A: withdraws an open swap and returns its deposit, once
+    /// side B confirms it was not accepted. Only the position holder
+    /// may cancel a tokenized swap, the maker or its refund address
+    /// any other.
+    CancelSwap { id: u64 },
+    /// Owner: sets the cw721 contract positions are minted by. This
+    /// contract must be its minter.
+    SetPositionNft { address: String },
+    /// Registers a contract to receive a `SwapHookMsg` on every swap
+    /// transition. The owner adds any contract for free, other
+    /// contracts register themselves by paying the hook fee. At most
+    /// ten hooks may be registered.
+    AddHook { addr: String },
+    /// Owner, or the hook itself: stops notifying a hook.
+    RemoveHook { addr: String },
+    /// Owner: sets the fee contracts pay to register as a hook, or
+    /// restricts registration to the owner with `None`.
+    SetHookFee { fee: Option<Coin> },
+    /// Owner: sets the gas each hook may use per notification.
+    SetHookGasLimit { gas_limit: u64 },
+    /// Owner: sets the oracle new swaps are priced against, or removes
+    /// it with `None`.
+    SetPriceOracle { oracle: Option<PriceOracleMsg> },
+    /// Owner: sets the rate limit for a denom on a channel, or clears
+    /// it with `None`. Deposits paid out count against the swap's
+    /// `deposit_transfer_channel_id`, asks against its
+    /// `ask_transfer_channel_id`, and listings received against the
+    /// swap channel they arrive on.
+    SetRateLimit {
+        channel_id: String,
+        denom: String,
+        limit: Option<RateLimit>,
+    },
+    /// Side B: pays out a maker's payment queued by a rate limit, once
+    /// the limit allows it. Anyone may trigger it.
+    ReleasePayout { id: u64 },
+    /// Returns the escrow of an HTLC swap whose time lock expired, the
+    /// deposit to the maker on side A or the payment to the taker on
+    /// side B. Anyone may trigger it.
+    RefundSwap { side: String, id: u64 },
+}
+
+#[cw_serde]
+pub struct PriceOracleMsg {
+    pub address: String,
+    /// Largest relative deviation from the oracle's price allowed,
+    /// `0.5` lets a swap ask up to 50% more or less than the oracle.
+    pub tolerance: Decimal,
+    /// Rejects deviating swaps when set, otherwise they are only
+    /// flagged in their `swap_created` event.
+    pub enforce: bool,
+}
+
+/// Swap transition sent to hook contracts, wrapped in
+/// `SwapHookExecuteMsg`. `side` is the side the transition happened
+/// on, a swap settles once on each.
+#[cw_serde]
+pub enum SwapHookMsg {
+    /// Side A escrowed the deposit of a new swap.
+    SwapCreated { id: u64, side: String, swap: Swap },
+    /// A side released the funds it escrowed to their counterparty.
+    SwapSettled { id: u64, side: String, swap: Swap },
+    /// A side returned the funds it escrowed to their owner.
+    SwapCancelled { id: u64, side: String, swap: Swap },
+}
+
+/// Execute message hook contracts are called with.
+#[cw_serde]
+pub enum SwapHookExecuteMsg {
+    SwapHook(SwapHookMsg),
+}
+
+/// Query answered by the price oracle.
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    /// Price of one unit of `base_denom` in units of `quote_denom`.
+    #[returns(PriceResponse)]
+    Price {
+        base_denom: String,
+        quote_denom: String,
+    },
+}
+
+#[cw_serde]
+pub struct PriceResponse {
+    pub price: Decimal,
+}
+
+/// One pool hop of a swap router route.
+#[cw_serde]
+pub struct SwapAmountInRoute {
+    pub pool_id: u64,
+    pub token_out_denom: String,
+}
+
+/// Message executed on the swap router, after the Osmosis swaprouter
+/// contract.
+#[cw_serde]
+pub enum SwapRouterMsg {
+    Swap {
+        input_coin: Coin,
+        output_denom: String,
+        minimum_output_amount: Uint128,
+        route: Vec<SwapAmountInRoute>,
     },
 }
 
+/// Message of a cw721 `SendNft` to this contract.
+#[cw_serde]
+pub enum ReceiveNftMsg {
+    /// Side A: escrows the NFT as the deposit of a new swap.
+    CreateSwap {
+        ask: Token,
+        /// ICS-721 channel the NFT is sent to the taker over.
+        deposit_transfer_channel_id: String,
+        ask_transfer_channel_id: String,
+        recipient: Option<String>,
+        #[serde(default)]
+        allowed_takers: Option<TakerAllowlist>,
+    },
+    /// Side B: pays a swap asking for the NFT with it.
+    AcceptSwap { id: u64, recipient: Option<String> },
+}
+
+/// Message of a cw721 `SendNft` to the ICS-721 bridge, sending the NFT
+/// to `receiver` over `channel_id`.
+#[cw_serde]
+pub struct Ics721OutgoingMsg {
+    pub receiver: String,
+    pub channel_id: String,
+    pub timeout: IbcTimeout,
+}
+
+/// One swap of a `BatchCreateSwaps`, with the same options as
+/// `CreateSwap`.
+#[cw_serde]
+pub struct SwapOffer {
+    /// Share of the funds sent escrowed by this swap.
+    pub deposit: Token,
+    pub ask: Token,
+    pub deposit_transfer_channel_id: String,
+    pub ask_transfer_channel_id: String,
+    pub htlc: Option<HtlcMsg>,
+    pub dutch_auction: Option<DutchAuctionMsg>,
+    pub english_auction: Option<EnglishAuctionMsg>,
+    pub recipient: Option<String>,
+    pub refund_address: Option<String>,
+    #[serde(default)]
+    pub allowed_takers: Option<TakerAllowlist>,
+    #[serde(default)]
+    pub local: bool,
+}
+
+#[cw_serde]
+pub struct DutchAuctionMsg {
+    /// Lowest amount of the ask denom the maker accepts.
+    pub floor: Uint128,
+    /// Seconds from creation until the ask reaches the floor.
+    pub duration: u64,
+}
+
+#[cw_serde]
+pub struct EnglishAuctionMsg {
+    /// Seconds from creation until bidding closes.
+    pub duration: u64,
+}
+
+#[cw_serde]
+pub struct HtlcMsg {
+    /// sha256 digest of a secret preimage chosen by the maker.
+    pub hashlock: HexBinary,
+    /// Seconds until the maker may reclaim the deposit. Takers must
+    /// accept and be paid within the first half.
+    pub timelock: u64,
+}
+
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     // GetCount returns the current count as a json-encoded number
     #[returns(Swap)]
     GetSwap { side: String, id: u64 },
+    /// Current ask of a swap, which declines for Dutch auctions.
+    #[returns(Token)]
+    CurrentAsk { side: String, id: u64 },
+    /// Owner, packet lifetime and the channel this contract is bound to.
+    #[returns(State)]
+    Config {},
+    /// Contract version, IBC protocol versions and supported features.
+    #[returns(ContractInfoResponse)]
+    ContractInfo {},
+    /// Channel handshakes the owner authorized.
+    #[returns(AuthorizedChannelsResponse)]
+    AuthorizedChannels {},
+    /// Owner's denom rules in ascending denom order.
+    #[returns(DenomRulesResponse)]
+    DenomRules {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// ICS-721 bridge NFTs are sent across chains with, if any.
+    #[returns(Option<cosmwasm_std::Addr>)]
+    NftBridge {},
+    /// cw721 contracts NFTs may be swapped from.
+    #[returns(NftCollectionsResponse)]
+    NftCollections {},
+    /// cw721 contract positions are minted by, if any.
+    #[returns(Option<cosmwasm_std::Addr>)]
+    PositionNft {},
+    /// Contracts notified of swap transitions.
+    #[returns(cw_controllers::HooksResponse)]
+    Hooks {},
+    /// Fee contracts pay to register as a hook, if they may.
+    #[returns(Option<Coin>)]
+    HookFee {},
+    /// Gas each hook may use per notification.
+    #[returns(u64)]
+    HookGasLimit {},
+    /// Oracle new swaps are priced against, if any.
+    #[returns(Option<PriceOracle>)]
+    PriceOracle {},
+    /// Rate limit of a denom on a channel and the rolling totals
+    /// counted against it.
+    #[returns(RateLimitResponse)]
+    RateLimit { channel_id: String, denom: String },
+    /// Swaps settled on side B whose payout waits for a rate limit.
+    #[returns(ListSwapsResponse)]
+    QueuedPayouts {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Lifecycle counters and cumulative volume per denom and direction.
+    #[returns(StatsResponse)]
+    Stats {},
+    /// What the contract owes of each denom against its bank balance.
+    #[returns(SolvencyResponse)]
+    Solvency {},
+    /// Pages through the swaps on one side in ascending id order,
+    /// optionally restricted to a maker, denoms and status.
+    #[returns(ListSwapsResponse)]
+    ListSwaps {
+        side: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        maker: Option<String>,
+        deposit_denom: Option<String>,
+        ask_denom: Option<String>,
+        status: Option<SwapStatus>,
+    },
+    /// Open requests for quote on one side, in ascending id order.
+    #[returns(ListRequestsResponse)]
+    ListRequests {
+        side: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Quotes listed on side B for a request made on this chain.
+    #[returns(ListSwapsResponse)]
+    Quotes {
+        rfq_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Swaps whose deposit was made by `maker`.
+    #[returns(ListSwapsResponse)]
+    SwapsByMaker {
+        side: String,
+        maker: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Swaps reserved for `taker` through their `ask_address`.
+    #[returns(ListSwapsResponse)]
+    SwapsByTaker {
+        side: String,
+        taker: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Swaps offering `deposit_denom` in exchange for `ask_denom`.
+    #[returns(ListSwapsResponse)]
+    SwapsByDenomPair {
+        side: String,
+        deposit_denom: String,
+        ask_denom: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+#[cw_serde]
+pub struct SwapItem {
+    pub id: u64,
+    pub swap: Swap,
+}
+
+#[cw_serde]
+pub struct ListSwapsResponse {
+    pub swaps: Vec<SwapItem>,
+}
+
+#[cw_serde]
+pub struct RfqItem {
+    pub id: u64,
+    pub rfq: Rfq,
+}
+
+#[cw_serde]
+pub struct ListRequestsResponse {
+    pub requests: Vec<RfqItem>,
+}
+
+#[cw_serde]
+pub struct AuthorizedChannel {
+    pub connection_id: String,
+    pub counterparty_port_id: String,
+}
+
+#[cw_serde]
+pub struct AuthorizedChannelsResponse {
+    pub channels: Vec<AuthorizedChannel>,
+}
+
+#[cw_serde]
+pub struct NftCollectionsResponse {
+    pub collections: Vec<Addr>,
+}
+
+#[cw_serde]
+pub struct DenomRuleItem {
+    pub denom: String,
+    pub rule: DenomRule,
+}
+
+#[cw_serde]
+pub struct DenomRulesResponse {
+    /// Whether denoms without a rule are refused.
+    pub allowlist: bool,
+    pub rules: Vec<DenomRuleItem>,
+}
+
+#[cw_serde]
+pub struct RateLimitResponse {
+    pub limit: Option<RateLimit>,
+    /// Rolling total of listings received.
+    pub inbound: Uint128,
+    /// Rolling total paid out.
+    pub outbound: Uint128,
+}
+
+#[cw_serde]
+pub struct ContractInfoResponse {
+    /// cw2 contract name.
+    pub contract: String,
+    /// cw2 contract version.
+    pub version: String,
+    /// Highest IBC protocol version, proposed when opening channels.
+    pub ibc_version: String,
+    pub supported_ibc_versions: Vec<String>,
+    /// Version negotiated on the bound channel, if any.
+    pub channel_version: Option<String>,
+    pub features: Vec<String>,
 }
 
 #[cw_serde]
+pub struct DenomVolume {
+    /// `in` for funds escrowed here, `out` for funds paid out to settle
+    /// swaps, `refunded` for funds returned to their owner.
+    pub direction: String,
+    pub denom: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct StatsResponse {
+    pub stats: Stats,
+    pub volume: Vec<DenomVolume>,
+}
+
+#[cw_serde]
+pub struct DenomSolvency {
+    pub denom: String,
+    /// Escrowed for makers and takers.
+    pub owed: Uint128,
+    /// Held by the contract. Anything on top of `owed` belongs to no
+    /// swap, like ICS-20 transfers refunded after a timeout.
+    pub balance: Uint128,
+}
+
+#[cw_serde]
+pub struct SolvencyResponse {
+    /// Whether the contract holds at least what it owes of every denom.
+    pub solvent: bool,
+    pub denoms: Vec<DenomSolvency>,
+}
+
+#[cw_serde]
+#[allow(clippy::large_enum_variant)]
 pub enum PacketMsg {
-    CreateSideB { id: u64, swap: Swap },
-    AcceptSideA { id: u64, sender: String },
+    CreateSideB {
+        id: u64,
+        swap: Swap,
+    },
+    AcceptSideA {
+        id: u64,
+        sender: String,
+        /// Price an auction was settled at on side B.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        price: Option<Uint128>,
+        /// Paid the deposit instead of `sender`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        recipient: Option<String>,
+    },
+    RevealSideA {
+        id: u64,
+        preimage: HexBinary,
+    },
+    /// Side B withdrew the listing, return the deposit to the maker.
+    RefundSideA {
+        id: u64,
+    },
+    /// Lists a request for quote made on side B.
+    RequestQuote {
+        id: u64,
+        rfq: Rfq,
+    },
+    /// The request was accepted or withdrawn, stop quoting it.
+    CloseRequest {
+        id: u64,
+    },
+    /// The maker cancelled the swap, withdraw the listing if it is
+    /// still open.
+    CancelSideB {
+        id: u64,
+    },
+}
+
+/// Acknowledgement of an `AcceptSideA` for a tokenized swap, naming
+/// the position holder the ask is paid to. Other accepts are
+/// acknowledged with the swap id.
+#[cw_serde]
+pub struct AcceptAck {
+    pub id: u64,
+    pub payee: String,
+}
+
+/// Wire format of packets on channels that negotiated `native-swap-2`
+/// or later. Channels still on `native-swap-1` exchange a bare
+/// `PacketMsg`.
+#[cw_serde]
+pub struct PacketEnvelope {
+    pub version: String,
+    pub msg: PacketMsg,
 }
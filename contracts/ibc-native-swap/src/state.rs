@@ -1,13 +1,59 @@
 use cosmwasm_schema::cw_serde;
 use cw20::Denom;
 
-use cosmwasm_std::{Addr, IbcEndpoint, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{
+    Addr, Coin, Decimal, Empty, HexBinary, IbcEndpoint, StdError, StdResult, Storage, Timestamp,
+    Uint128,
+};
+use cw_controllers::Hooks;
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use sha2::{Digest, Sha256};
 
 #[cw_serde]
 pub struct Token {
     pub denom: Denom,
     pub amount: Uint128,
+    /// Set for a cw721 token, whose contract is the `Cw20` denom and
+    /// whose amount is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+}
+
+impl Token {
+    /// A cw721 token, escrowed through `ReceiveNft` and carried across
+    /// chains by the ICS-721 bridge.
+    pub fn nft(contract: &str, token_id: String) -> Self {
+        Token {
+            denom: Denom::Cw20(Addr::unchecked(contract)),
+            amount: Uint128::one(),
+            token_id: Some(token_id),
+        }
+    }
+
+    pub fn is_nft(&self) -> bool {
+        self.token_id.is_some()
+    }
+
+    /// The native coin this token stands for. cw20 tokens are not
+    /// supported yet, and an NFT is never a coin.
+    pub fn to_coin(&self) -> StdResult<Coin> {
+        if let Some(token_id) = &self.token_id {
+            return Err(StdError::generic_err(format!(
+                "NFT {} is not a coin",
+                token_id
+            )));
+        }
+        match &self.denom {
+            Denom::Native(denom) => Ok(Coin {
+                denom: denom.clone(),
+                amount: self.amount,
+            }),
+            Denom::Cw20(addr) => Err(StdError::generic_err(format!(
+                "cw20 token {} is not supported",
+                addr
+            ))),
+        }
+    }
 }
 
 #[cw_serde]
@@ -16,6 +62,30 @@ pub struct State {
     pub packet_lifetime: u64,
     pub endpoint: Option<IbcEndpoint>,
     pub counterparty_endpoint: Option<IbcEndpoint>,
+    /// Channel version negotiated during the handshake.
+    pub ibc_version: Option<String>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum SwapStatus {
+    /// Escrowed on side A, waiting for side B to acknowledge the listing.
+    Pending,
+    /// Listed on both sides and available to accept.
+    #[default]
+    Open,
+    /// Accepted by a taker, waiting for the escrow to be released.
+    Accepted,
+}
+
+impl SwapStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwapStatus::Pending => "pending",
+            SwapStatus::Open => "open",
+            SwapStatus::Accepted => "accepted",
+        }
+    }
 }
 
 #[cw_serde]
@@ -26,12 +96,511 @@ pub struct Swap {
     pub ask: Token,
     pub ask_address: Option<Addr>,
     pub ask_transfer_channel_id: String,
+    #[serde(default)]
+    pub status: SwapStatus,
+    /// Set when the swap was created in HTLC mode.
+    #[serde(default)]
+    pub htlc: Option<Htlc>,
+    /// Address that accepted the swap, once known on this side.
+    #[serde(default)]
+    pub taker: Option<Addr>,
+    /// Set when `ask` declines over time. `ask` holds the starting
+    /// price until the swap is accepted, and the agreed price after.
+    #[serde(default)]
+    pub dutch_auction: Option<DutchAuction>,
+    /// Set when takers bid for the deposit on side B. `ask` holds the
+    /// reserve price until the auction is finalized, and the winning
+    /// bid after.
+    #[serde(default)]
+    pub english_auction: Option<EnglishAuction>,
+    /// Set when the swap quotes the request for quote with this id,
+    /// reserved for its requester through `ask_address`.
+    #[serde(default)]
+    pub rfq_id: Option<u64>,
+    /// Paid the ask instead of `deposit_address`.
+    #[serde(default)]
+    pub recipient: Option<Addr>,
+    /// Paid the deposit instead of `taker`.
+    #[serde(default)]
+    pub taker_recipient: Option<Addr>,
+    /// Refunded the deposit instead of `deposit_address`.
+    #[serde(default)]
+    pub refund_address: Option<Addr>,
+    /// Takers allowed to accept the swap, on top of `ask_address`.
+    #[serde(default)]
+    pub allowed_takers: Option<TakerAllowlist>,
+    /// Listed and settled on side A only, never sent to side B.
+    #[serde(default)]
+    pub local: bool,
+}
+
+impl Swap {
+    /// Where the maker is paid the ask.
+    pub fn maker_payee(&self) -> &Addr {
+        self.recipient.as_ref().unwrap_or(&self.deposit_address)
+    }
+
+    /// Where the maker is refunded the deposit.
+    pub fn refund_payee(&self) -> &Addr {
+        self.refund_address
+            .as_ref()
+            .unwrap_or(&self.deposit_address)
+    }
+
+    /// Where the taker is paid the deposit, once known.
+    pub fn taker_payee(&self) -> Option<&Addr> {
+        self.taker_recipient.as_ref().or(self.taker.as_ref())
+    }
+
+    /// What the maker asks for at `time`.
+    pub fn ask_at(&self, time: Timestamp) -> Token {
+        match (&self.dutch_auction, &self.status) {
+            (Some(auction), SwapStatus::Pending | SwapStatus::Open) => Token {
+                denom: self.ask.denom.clone(),
+                amount: auction.price(self.ask.amount, time),
+                token_id: None,
+            },
+            _ => self.ask.clone(),
+        }
+    }
+
+    /// Whether side A settles for `price` as agreed on side B, which is
+    /// only negotiable for auctions.
+    pub fn accepts_price(&self, price: Uint128) -> bool {
+        match (&self.dutch_auction, &self.english_auction) {
+            (Some(auction), _) => price >= auction.floor,
+            (_, Some(_)) => price >= self.ask.amount,
+            (None, None) => false,
+        }
+    }
+}
+
+/// Ascending auction for a swap's deposit, run on side B.
+#[cw_serde]
+pub struct EnglishAuction {
+    /// Bids are taken until then, after which anyone may finalize.
+    pub deadline: Timestamp,
+    /// Highest bid so far, escrowed on side B.
+    pub highest_bid: Option<Bid>,
+}
+
+#[cw_serde]
+pub struct Bid {
+    pub bidder: Addr,
+    pub amount: Uint128,
+}
+
+/// Schedule of an ask declining linearly to a floor.
+#[cw_serde]
+pub struct DutchAuction {
+    /// Lowest price, asked from `end_time` on.
+    pub floor: Uint128,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+}
+
+impl DutchAuction {
+    /// Price at `time` of an auction starting at `start`.
+    pub fn price(&self, start: Uint128, time: Timestamp) -> Uint128 {
+        if time <= self.start_time {
+            return start;
+        }
+        if time >= self.end_time {
+            return self.floor;
+        }
+        let elapsed = time.seconds() - self.start_time.seconds();
+        let duration = self.end_time.seconds() - self.start_time.seconds();
+        start - (start - self.floor).multiply_ratio(elapsed, duration)
+    }
+}
+
+/// Takers a maker restricts a swap to, checked where it is accepted.
+/// Addresses live on that chain, side B unless the swap is local.
+#[cw_serde]
+pub enum TakerAllowlist {
+    Addresses(Vec<String>),
+    /// Members of a cw4 group contract, with any weight.
+    Cw4Group(String),
+}
+
+/// Hash and time locks of a swap created in HTLC mode. The deposit on
+/// side A and the taker's payment on side B are only released with the
+/// preimage of `hashlock`, and go back to their owner once the time
+/// lock of their side expires. Contracts can not schedule their own
+/// execution, so the refund is permissionless rather than timed:
+/// anyone, typically a keeper or the owner, sends `RefundSwap`.
+#[cw_serde]
+pub struct Htlc {
+    /// sha256 digest of a secret only the maker knows.
+    pub hashlock: HexBinary,
+    /// When the maker may reclaim the deposit on side A.
+    pub maker_expires: Timestamp,
+    /// When the taker may reclaim the payment on side B. Comes first,
+    /// so the taker can still claim the deposit with a preimage the
+    /// maker revealed just before it.
+    pub taker_expires: Timestamp,
+}
+
+impl Htlc {
+    pub fn verify(&self, preimage: &[u8]) -> bool {
+        Sha256::digest(preimage).as_slice() == self.hashlock.as_slice()
+    }
+}
+
+/// Key used to index a denom, the native denom or the cw20 address.
+pub fn denom_key(denom: &Denom) -> String {
+    match denom {
+        Denom::Native(denom) => denom.clone(),
+        Denom::Cw20(addr) => addr.to_string(),
+    }
 }
 
 pub const STATE: Item<State> = Item::new("state");
 
+/// Owner's rule for swapping a denom, checked against every deposit
+/// and ask.
+#[cw_serde]
+#[derive(Default)]
+pub struct DenomRule {
+    /// Denied denoms can not be swapped at all.
+    pub denied: bool,
+    pub min_amount: Option<Uint128>,
+    pub max_amount: Option<Uint128>,
+}
+
+pub const DENOM_RULES: Map<&str, DenomRule> = Map::new("denom_rules");
+
+/// Whether only denoms with a rule may be swapped.
+pub const DENOM_ALLOWLIST: Item<bool> = Item::new("denom_allowlist");
+
+/// Channel handshakes the owner expects, keyed by connection id and
+/// counterparty port id. No other channel may be opened.
+pub const AUTHORIZED_CHANNELS: Map<(&str, &str), Empty> = Map::new("authorized_channels");
+
+/// Owner's quota on the amount of a denom crossing a channel in each
+/// direction over a rolling window.
+#[cw_serde]
+pub struct RateLimit {
+    /// Window length in seconds.
+    pub window: u64,
+    pub max_amount: Uint128,
+}
+
+/// Rate limits keyed by channel and denom.
+pub const RATE_LIMITS: Map<(&str, &str), RateLimit> = Map::new("rate_limits");
+
+/// Amounts counted against a rate limit in the current and previous
+/// fixed windows. The rolling total weighs the previous window by the
+/// share of it still inside the rolling window.
+#[cw_serde]
+#[derive(Default)]
+pub struct Flow {
+    /// Index of the current window, in window lengths since epoch.
+    pub window: u64,
+    pub current: Uint128,
+    pub previous: Uint128,
+}
+
+impl Flow {
+    /// Moves the windows forward to `time` and returns the rolling total.
+    pub fn advance(&mut self, limit: &RateLimit, time: Timestamp) -> Uint128 {
+        let now = time.seconds();
+        let window = now / limit.window;
+        match window.checked_sub(self.window) {
+            Some(0) => {}
+            Some(1) => {
+                self.previous = self.current;
+                self.current = Uint128::zero();
+            }
+            _ => {
+                self.previous = Uint128::zero();
+                self.current = Uint128::zero();
+            }
+        }
+        self.window = window;
+        let remaining = limit.window - now % limit.window;
+        self.previous.multiply_ratio(remaining, limit.window) + self.current
+    }
+}
+
+/// Flows keyed by direction, channel and denom.
+pub const FLOWS: Map<(&str, &str, &str), Flow> = Map::new("flows");
+
+/// Counts `token` against the rate limit of its denom on `channel`, if
+/// any. Returns false without counting it if that would exceed the
+/// limit.
+pub fn consume_flow(
+    storage: &mut dyn Storage,
+    time: Timestamp,
+    direction: &str,
+    channel: &str,
+    token: &Token,
+) -> StdResult<bool> {
+    let denom = denom_key(&token.denom);
+    let limit = match RATE_LIMITS.may_load(storage, (channel, &denom))? {
+        Some(limit) => limit,
+        None => return Ok(true),
+    };
+    let key = (direction, channel, denom.as_str());
+    let mut flow = FLOWS.may_load(storage, key)?.unwrap_or_default();
+    if flow.advance(&limit, time).checked_add(token.amount)? > limit.max_amount {
+        return Ok(false);
+    }
+    flow.current = flow.current.checked_add(token.amount)?;
+    FLOWS.save(storage, key, &flow)?;
+    Ok(true)
+}
+
+/// Oracle the price of new swaps is checked against.
+#[cw_serde]
+pub struct PriceOracle {
+    pub address: Addr,
+    /// Largest relative deviation from the oracle's price allowed.
+    pub tolerance: Decimal,
+    /// Whether deviating swaps are rejected, or only flagged in their
+    /// `swap_created` event.
+    pub enforce: bool,
+}
+
+pub const PRICE_ORACLE: Item<PriceOracle> = Item::new("price_oracle");
+
+/// ICS-721 bridge NFT deposits and asks are sent across chains with.
+pub const NFT_BRIDGE: Item<Addr> = Item::new("nft_bridge");
+
+/// cw721 contracts the owner allows NFTs to be swapped from.
+pub const NFT_COLLECTIONS: Map<&Addr, Empty> = Map::new("nft_collections");
+
+/// cw721 contract minting swap positions, with this contract as its
+/// minter.
+pub const POSITION_NFT: Item<Addr> = Item::new("position_nft");
+
+/// Side A swaps whose proceeds belong to the holder of an NFT, with
+/// the cw721 contract it was minted by. The token id is the swap id.
+pub const POSITIONS: Map<u64, Addr> = Map::new("positions");
+
+/// Contracts notified of every swap transition with a `SwapHookMsg`.
+pub const HOOKS: Hooks = Hooks::new("hooks");
+
+/// Fee a contract pays to register itself as a hook. Without it only
+/// the owner may add hooks.
+pub const HOOK_FEE: Item<Coin> = Item::new("hook_fee");
+
+/// Gas each hook may use per notification, set by the owner. A hook
+/// running out of it fails on its own like any other error.
+pub const HOOK_GAS_LIMIT: Item<u64> = Item::new("hook_gas_limit");
+pub const DEFAULT_HOOK_GAS_LIMIT: u64 = 300_000;
+
+/// Most hooks that may be registered, which bounds the gas every swap
+/// transition spends notifying them.
+pub const MAX_HOOKS: usize = 10;
+
+/// Router `AcceptSwapWithRoute` buys asks through.
+pub const SWAP_ROUTER: Item<Addr> = Item::new("swap_router");
+
+/// An accept waiting for the swap router to buy its ask.
+#[cw_serde]
+pub struct RoutedAccept {
+    pub id: u64,
+    pub taker: Addr,
+    /// Balance of the ask denom before the router paid it in.
+    pub balance: Uint128,
+}
+
+pub const ROUTED_ACCEPT: Item<RoutedAccept> = Item::new("routed_accept");
+
+/// Side B payouts to makers held back by the rate limit of the ask's
+/// transfer channel, with the channel they were settled over.
+#[cw_serde]
+pub struct Payout {
+    pub channel_id: String,
+    pub swap: Swap,
+}
+
+pub const PAYOUTS: Map<u64, Payout> = Map::new("payouts");
+
 pub const SWAP_ID: Item<u64> = Item::new("swap_id");
 
-pub const SWAPS_A: Map<u64, Swap> = Map::new("swaps_a");
+/// Request for quote published by a taker on side B, answered by
+/// makers on side A with swaps reserved for the requester.
+#[cw_serde]
+pub struct Rfq {
+    pub requester: Addr,
+    /// What the requester wants from makers on side A.
+    pub want: Token,
+    /// What the requester pays with on side B.
+    pub pay_denom: Denom,
+}
+
+pub const RFQ_ID: Item<u64> = Item::new("rfq_id");
+/// Open requests relayed by the counterparty, for makers to quote.
+pub const RFQS_A: Map<u64, Rfq> = Map::new("rfqs_a");
+/// Open requests made on this chain.
+pub const RFQS_B: Map<u64, Rfq> = Map::new("rfqs_b");
 
-pub const SWAPS_B: Map<u64, Swap> = Map::new("swaps_b");
+/// Running totals of swap lifecycle transitions seen by this contract.
+#[cw_serde]
+#[derive(Default)]
+pub struct Stats {
+    pub swaps_created: u64,
+    pub swaps_settled: u64,
+    /// Withdrawn by their maker, or the holder of their position.
+    pub swaps_cancelled: u64,
+    /// Returned to their owner once refused, withdrawn by side B or
+    /// expired.
+    pub swaps_refunded: u64,
+    /// English auction bids paid back once outbid.
+    #[serde(default)]
+    pub bids_refunded: u64,
+}
+
+pub const STATS: Item<Stats> = Item::new("stats");
+
+/// Funds escrowed by this contract.
+pub const VOLUME_IN: &str = "in";
+/// Funds paid out of this contract's escrow to settle swaps.
+pub const VOLUME_OUT: &str = "out";
+/// Funds returned from this contract's escrow to their owner.
+pub const VOLUME_REFUNDED: &str = "refunded";
+
+/// Cumulative volume keyed by direction and denom.
+pub const VOLUME: Map<(&str, &str), Uint128> = Map::new("volume");
+
+/// Native coins this contract owes, keyed by denom: deposits escrowed
+/// for makers, and payments and bids held for takers.
+pub const ESCROW: Map<&str, Uint128> = Map::new("escrow");
+
+pub fn update_stats(storage: &mut dyn Storage, action: impl FnOnce(&mut Stats)) -> StdResult<()> {
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    action(&mut stats);
+    STATS.save(storage, &stats)
+}
+
+pub fn record_volume(storage: &mut dyn Storage, direction: &str, token: &Token) -> StdResult<()> {
+    let denom = denom_key(&token.denom);
+    VOLUME.update(storage, (direction, &denom), |volume| -> StdResult<_> {
+        Ok(volume.unwrap_or_default().checked_add(token.amount)?)
+    })?;
+    Ok(())
+}
+
+/// Records `token` entering escrow. NFTs stay with their own contract,
+/// so only native coins are counted.
+pub fn escrow_in(storage: &mut dyn Storage, token: &Token) -> StdResult<()> {
+    if let (Denom::Native(denom), None) = (&token.denom, &token.token_id) {
+        ESCROW.update(storage, denom, |owed| -> StdResult<_> {
+            Ok(owed.unwrap_or_default().checked_add(token.amount)?)
+        })?;
+    }
+    Ok(())
+}
+
+/// Records `token` leaving escrow. Paying out more than is owed fails.
+pub fn escrow_out(storage: &mut dyn Storage, token: &Token) -> StdResult<()> {
+    if let (Denom::Native(denom), None) = (&token.denom, &token.token_id) {
+        let owed = ESCROW
+            .may_load(storage, denom)?
+            .unwrap_or_default()
+            .checked_sub(token.amount)?;
+        if owed.is_zero() {
+            ESCROW.remove(storage, denom);
+        } else {
+            ESCROW.save(storage, denom, &owed)?;
+        }
+    }
+    Ok(())
+}
+
+pub struct SwapIndexes<'a> {
+    pub maker: MultiIndex<'a, Addr, Swap, u64>,
+    /// Designated taker, or an empty address for swaps anyone may accept.
+    pub taker: MultiIndex<'a, Addr, Swap, u64>,
+    pub deposit_denom: MultiIndex<'a, String, Swap, u64>,
+    pub ask_denom: MultiIndex<'a, String, Swap, u64>,
+    pub denom_pair: MultiIndex<'a, (String, String), Swap, u64>,
+    pub status: MultiIndex<'a, String, Swap, u64>,
+}
+
+impl<'a> IndexList<Swap> for SwapIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Swap>> + '_> {
+        let v: Vec<&dyn Index<Swap>> = vec![
+            &self.maker,
+            &self.taker,
+            &self.deposit_denom,
+            &self.ask_denom,
+            &self.denom_pair,
+            &self.status,
+        ];
+        Box::new(v.into_iter())
+    }
+}
+
+impl<'a> SwapIndexes<'a> {
+    /// `idx_namespaces` holds the namespaces of the maker, taker,
+    /// deposit denom, ask denom, denom pair and status indexes.
+    fn new(pk_namespace: &'a str, idx_namespaces: [&'a str; 6]) -> Self {
+        let [maker, taker, deposit_denom, ask_denom, denom_pair, status] = idx_namespaces;
+        SwapIndexes {
+            maker: MultiIndex::new(|_pk, s| s.deposit_address.clone(), pk_namespace, maker),
+            taker: MultiIndex::new(
+                |_pk, s| s.ask_address.clone().unwrap_or_else(|| Addr::unchecked("")),
+                pk_namespace,
+                taker,
+            ),
+            deposit_denom: MultiIndex::new(
+                |_pk, s| denom_key(&s.deposit.denom),
+                pk_namespace,
+                deposit_denom,
+            ),
+            ask_denom: MultiIndex::new(|_pk, s| denom_key(&s.ask.denom), pk_namespace, ask_denom),
+            denom_pair: MultiIndex::new(
+                |_pk, s| (denom_key(&s.deposit.denom), denom_key(&s.ask.denom)),
+                pk_namespace,
+                denom_pair,
+            ),
+            status: MultiIndex::new(|_pk, s| s.status.as_str().to_string(), pk_namespace, status),
+        }
+    }
+}
+
+/// Swaps made on this chain, escrowing the maker's deposit.
+pub fn swaps_a<'a>() -> IndexedMap<'a, u64, Swap, SwapIndexes<'a>> {
+    let indexes = SwapIndexes::new(
+        "swaps_a",
+        [
+            "swaps_a__maker",
+            "swaps_a__taker",
+            "swaps_a__deposit_denom",
+            "swaps_a__ask_denom",
+            "swaps_a__denom_pair",
+            "swaps_a__status",
+        ],
+    );
+    IndexedMap::new("swaps_a", indexes)
+}
+
+/// Swaps listed by the counterparty, accepted on this chain.
+pub fn swaps_b<'a>() -> IndexedMap<'a, u64, Swap, SwapIndexes<'a>> {
+    let indexes = SwapIndexes::new(
+        "swaps_b",
+        [
+            "swaps_b__maker",
+            "swaps_b__taker",
+            "swaps_b__deposit_denom",
+            "swaps_b__ask_denom",
+            "swaps_b__denom_pair",
+            "swaps_b__status",
+        ],
+    );
+    IndexedMap::new("swaps_b", indexes)
+}
+
+/// The swaps of side `A` or `B`.
+pub fn swaps_for_side<'a>(side: &str) -> StdResult<IndexedMap<'a, u64, Swap, SwapIndexes<'a>>> {
+    if side == "A" {
+        Ok(swaps_a())
+    } else if side == "B" {
+        Ok(swaps_b())
+    } else {
+        Err(StdError::generic_err("Invalid side"))
+    }
+}
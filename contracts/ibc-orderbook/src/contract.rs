@@ -1,19 +1,39 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, IbcMsg, MessageInfo, Response, StdResult,
+    to_binary, Binary, Decimal, Deps, DepsMut, Empty, Env, IbcMsg, MessageInfo, Response, StdError,
+    StdResult,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw20::Denom;
 use cw_utils::must_pay;
+use semver::Version;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, PacketMsg, QueryMsg};
-use crate::state::{Limit, State, Token, LIMITS_A, LIMITS_B, LIMIT_ID, STATE};
+use crate::events::{limit_event, LIMIT_ACCEPTED, LIMIT_CREATED};
+use crate::ibc_helpers::encode_packet;
+use crate::migrations::{migrate_storage, V0_2_0};
+use crate::msg::{
+    ExecuteMsg, InstantiateMsg, MigrateMsg, OracleQueryMsg, PacketMsg, PriceOracleMsg,
+    PriceResponse, QueryMsg,
+};
+use crate::state::{
+    denom_key, Limit, PriceOracle, State, Token, AUTHORIZED_CHANNELS, LIMITS_A, LIMITS_B, LIMIT_ID,
+    PRICE_ORACLE, STATE,
+};
 
 // version info for migration info
-const CONTRACT_NAME: &str = "crates.io:ibc-native-swap";
+const CONTRACT_NAME: &str = "crates.io:ibc-orderbook";
+// name stored by 0.1.x releases, which shipped with the swap contract's name
+const LEGACY_CONTRACT_NAME: &str = "crates.io:ibc-native-swap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Optional capabilities advertised through the `ContractInfo` query.
+pub const FEATURES: &[&str] = &[
+    "versioned_packets",
+    "migrate",
+    "price_oracle",
+    "channel_authorization",
+];
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -27,6 +47,7 @@ pub fn instantiate(
         packet_lifetime: msg.packet_lifetime,
         endpoint: None,
         counterparty_endpoint: None,
+        ibc_version: None,
     };
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     STATE.save(deps.storage, &state)?;
@@ -37,6 +58,34 @@ pub fn instantiate(
         .add_attribute("owner", info.sender))
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    let previous_version: Version = stored.version.parse()?;
+    let legacy_name = stored.contract == LEGACY_CONTRACT_NAME && previous_version < V0_2_0;
+    if stored.contract != CONTRACT_NAME && !legacy_name {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: stored.contract,
+        });
+    }
+
+    let new_version: Version = CONTRACT_VERSION.parse()?;
+    if previous_version > new_version {
+        return Err(ContractError::CannotMigrateVersion {
+            previous_version: stored.version,
+            new_version: CONTRACT_VERSION.to_string(),
+        });
+    }
+
+    migrate_storage(deps.storage, &previous_version)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -59,10 +108,19 @@ pub fn execute(
         ),
         ExecuteMsg::AcceptLimit { id } => execute::accept_limit(deps, env, info, id),
         ExecuteMsg::UpdateLimit {
-            id,
-            price_per_token,
+            id: _,
+            price_per_token: _,
         } => unimplemented!(),
-        ExecuteMsg::RemoveLimit { id } => unimplemented!(),
+        ExecuteMsg::RemoveLimit { id: _ } => unimplemented!(),
+        ExecuteMsg::SetPriceOracle { oracle } => execute::set_price_oracle(deps, info, oracle),
+        ExecuteMsg::AuthorizeChannel {
+            connection_id,
+            counterparty_port_id,
+        } => execute::authorize_channel(deps, info, connection_id, counterparty_port_id, true),
+        ExecuteMsg::RevokeChannel {
+            connection_id,
+            counterparty_port_id,
+        } => execute::authorize_channel(deps, info, connection_id, counterparty_port_id, false),
     }
 }
 
@@ -92,6 +150,8 @@ pub mod execute {
             ask_transfer_channel_id: _ask_transfer_channel_id,
         };
 
+        let deviation = check_price(deps.as_ref(), &limit)?;
+
         LIMITS_A.save(deps.storage, limit_id, &limit)?;
 
         LIMIT_ID.save(deps.storage, &(limit_id.checked_add(1).unwrap()))?;
@@ -101,15 +161,143 @@ pub mod execute {
             limit: limit.clone(),
         };
 
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let mut event = limit_event(LIMIT_CREATED, limit_id, "A", &channel_id, &limit, None);
+        if let Some(oracle_price) = deviation {
+            event = event.add_attribute("oracle_price", oracle_price.to_string());
+        }
+
         let msg = IbcMsg::SendPacket {
-            channel_id: state.endpoint.unwrap().channel_id,
-            data: to_binary(&packet)?,
+            channel_id,
+            data: encode_packet(state.ibc_version.as_deref(), &packet)?,
             timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
         };
 
         Ok(Response::new()
             .add_message(msg)
-            .add_attribute("method", "create_limit"))
+            .add_attribute("method", "create_limit")
+            .add_event(event))
+    }
+
+    pub fn authorize_channel(
+        deps: DepsMut,
+        info: MessageInfo,
+        connection_id: String,
+        counterparty_port_id: String,
+        authorized: bool,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let key = (connection_id.as_str(), counterparty_port_id.as_str());
+        if authorized {
+            AUTHORIZED_CHANNELS.save(deps.storage, key, &Empty {})?;
+        } else {
+            AUTHORIZED_CHANNELS.remove(deps.storage, key);
+        }
+
+        Ok(Response::new()
+            .add_attribute(
+                "method",
+                if authorized {
+                    "authorize_channel"
+                } else {
+                    "revoke_channel"
+                },
+            )
+            .add_attribute("connection_id", connection_id)
+            .add_attribute("counterparty_port_id", counterparty_port_id))
+    }
+
+    pub fn set_price_oracle(
+        deps: DepsMut,
+        info: MessageInfo,
+        oracle: Option<PriceOracleMsg>,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+        let res = Response::new().add_attribute("method", "set_price_oracle");
+        match oracle {
+            Some(oracle) => {
+                let address = deps.api.addr_validate(&oracle.address)?;
+                let oracle = PriceOracle {
+                    address,
+                    tolerance: oracle.tolerance,
+                    enforce: oracle.enforce,
+                };
+                PRICE_ORACLE.save(deps.storage, &oracle)?;
+                Ok(res.add_attribute("oracle", oracle.address))
+            }
+            None => {
+                PRICE_ORACLE.remove(deps.storage);
+                Ok(res)
+            }
+        }
+    }
+
+    /// Compares the price of a limit order, what its liquidity is
+    /// offered for per unit of it, to the oracle's. Both count base
+    /// units, so the decimals of either denom cancel out. Orders
+    /// deviating by more than the tolerance are rejected, or the
+    /// oracle's price is returned to flag them. Orders the oracle can
+    /// not price are rejected, so that an outage does not let them
+    /// through unchecked.
+    pub fn check_price(deps: Deps, limit: &Limit) -> Result<Option<Decimal>, ContractError> {
+        let oracle = match PRICE_ORACLE.may_load(deps.storage)? {
+            Some(oracle) => oracle,
+            None => return Ok(None),
+        };
+        let base_denom = denom_key(&limit.liquidty.denom);
+        let quote_denom = denom_key(&limit.price_per_token.denom);
+        let query = OracleQueryMsg::Price {
+            base_denom: base_denom.clone(),
+            quote_denom: quote_denom.clone(),
+        };
+        let oracle_price = match deps
+            .querier
+            .query_wasm_smart::<PriceResponse>(&oracle.address, &query)
+        {
+            Ok(res) if !res.price.is_zero() => res.price,
+            _ => {
+                return Err(ContractError::NoOraclePrice {
+                    base_denom,
+                    quote_denom,
+                })
+            }
+        };
+        // What takers pay for all of the liquidity, against what they
+        // get. A price too large to represent is as far off as it gets.
+        let offer = limit.liquidty.amount;
+        let price = limit
+            .price_per_token
+            .amount
+            .checked_mul(offer)
+            .ok()
+            .and_then(|ask| Decimal::checked_from_ratio(ask, offer).ok());
+        let deviates = match price {
+            Some(price) => {
+                let diff = if price > oracle_price {
+                    price - oracle_price
+                } else {
+                    oracle_price - price
+                };
+                diff > oracle_price
+                    .checked_mul(oracle.tolerance)
+                    .map_err(StdError::from)?
+            }
+            None => true,
+        };
+        match (deviates, oracle.enforce) {
+            (false, _) => Ok(None),
+            (true, true) => Err(ContractError::PriceDeviation { oracle_price }),
+            (true, false) => Ok(Some(oracle_price)),
+        }
     }
 
     pub fn accept_limit(
@@ -122,9 +310,9 @@ pub mod execute {
 
         let limit = LIMITS_B.load(deps.storage, id)?;
 
-        match limit.price_per_token.denom {
+        match &limit.price_per_token.denom {
             Denom::Native(denom) => {
-                must_pay(&info, &denom).unwrap();
+                must_pay(&info, denom).unwrap();
             }
             Denom::Cw20(_) => unimplemented!(),
         };
@@ -134,7 +322,18 @@ pub mod execute {
             return Err(ContractError::InsufficientFunds {});
         }
 
-        let channel_id = state.endpoint.unwrap().channel_id;
+        let channel_id = state
+            .endpoint
+            .ok_or(ContractError::NotConnected {})?
+            .channel_id;
+        let event = limit_event(
+            LIMIT_ACCEPTED,
+            id,
+            "B",
+            &channel_id,
+            &limit,
+            Some((info.sender.as_str(), info.funds[0].amount)),
+        );
 
         let accept_msg = PacketMsg::AcceptLimitA {
             id,
@@ -143,14 +342,15 @@ pub mod execute {
         };
 
         let packet_msg = IbcMsg::SendPacket {
-            channel_id: channel_id,
-            data: to_binary(&accept_msg)?,
+            channel_id,
+            data: encode_packet(state.ibc_version.as_deref(), &accept_msg)?,
             timeout: env.block.time.plus_seconds(state.packet_lifetime).into(),
         };
 
         Ok(Response::new()
             .add_message(packet_msg)
-            .add_attribute("method", "accept_limit"))
+            .add_attribute("method", "accept_limit")
+            .add_event(event))
     }
 }
 
@@ -158,21 +358,336 @@ pub mod execute {
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetLimitOrder { side, id } => to_binary(&query::get_limit_order(deps, side, id)?),
+        QueryMsg::Config {} => to_binary(&query::config(deps)?),
+        QueryMsg::PriceOracle {} => to_binary(&PRICE_ORACLE.may_load(deps.storage)?),
+        QueryMsg::ContractInfo {} => to_binary(&query::contract_info(deps)?),
+        QueryMsg::AuthorizedChannels {} => to_binary(&query::authorized_channels(deps)?),
     }
 }
 
 pub mod query {
-    use cosmwasm_std::StdError;
+    use crate::ibc::{IBC_VERSION, IBC_VERSION_PREFIX, MAX_IBC_REVISION, MIN_IBC_REVISION};
+    use cosmwasm_std::Order;
+
+    use crate::msg::{AuthorizedChannel, AuthorizedChannelsResponse, ContractInfoResponse};
 
     use super::*;
 
+    pub fn config(deps: Deps) -> StdResult<State> {
+        STATE.load(deps.storage)
+    }
+
+    pub fn authorized_channels(deps: Deps) -> StdResult<AuthorizedChannelsResponse> {
+        let channels = AUTHORIZED_CHANNELS
+            .keys(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                item.map(|(connection_id, counterparty_port_id)| AuthorizedChannel {
+                    connection_id,
+                    counterparty_port_id,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(AuthorizedChannelsResponse { channels })
+    }
+
+    pub fn contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
+        let version = get_contract_version(deps.storage)?;
+        let state = STATE.load(deps.storage)?;
+        Ok(ContractInfoResponse {
+            contract: version.contract,
+            version: version.version,
+            ibc_version: IBC_VERSION.to_string(),
+            supported_ibc_versions: (MIN_IBC_REVISION..=MAX_IBC_REVISION)
+                .map(|revision| format!("{}{}", IBC_VERSION_PREFIX, revision))
+                .collect(),
+            channel_version: state.ibc_version,
+            features: FEATURES.iter().map(|f| f.to_string()).collect(),
+        })
+    }
+
     pub fn get_limit_order(deps: Deps, side: String, id: u64) -> StdResult<Limit> {
-        if side == "A".to_string() {
-            return Ok(LIMITS_A.load(deps.storage, id)?);
-        } else if side == "B".to_string() {
-            return Ok(LIMITS_B.load(deps.storage, id)?);
+        if side == "A" {
+            LIMITS_A.load(deps.storage, id)
+        } else if side == "B" {
+            LIMITS_B.load(deps.storage, id)
         } else {
-            return Err(StdError::generic_err("Invalid side"));
+            Err(StdError::generic_err("Invalid side"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_ibc_channel, mock_ibc_packet_recv, mock_info, MockApi,
+        MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        coins, from_binary, Addr, ContractResult, IbcChannel, IbcChannelConnectMsg,
+        IbcChannelOpenMsg, IbcEndpoint, IbcOrder, OwnedDeps, SystemError, SystemResult, Uint128,
+        WasmQuery,
+    };
+
+    use super::*;
+    use crate::ibc::{ibc_channel_connect, ibc_channel_open, ibc_packet_receive, IBC_VERSION};
+    use crate::ibc_helpers::StdAck;
+    use crate::msg::{AuthorizedChannel, AuthorizedChannelsResponse, PacketEnvelope};
+
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            InstantiateMsg {
+                packet_lifetime: 60,
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::AuthorizeChannel {
+                connection_id: "connection-2".to_string(),
+                counterparty_port_id: "their-port".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            mock_env(),
+            IbcChannelConnectMsg::new_ack(channel(), IBC_VERSION),
+        )
+        .unwrap();
+        deps
+    }
+
+    /// Channel bound by `setup`, whose counterparty end sends the
+    /// packets of `mock_ibc_packet_recv`.
+    fn channel() -> IbcChannel {
+        let mut channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, IBC_VERSION);
+        channel.counterparty_endpoint = IbcEndpoint {
+            port_id: "their-port".to_string(),
+            channel_id: "channel-1234".to_string(),
+        };
+        channel
+    }
+
+    fn token(denom: &str, amount: u128) -> Token {
+        Token {
+            denom: Denom::Native(denom.to_string()),
+            amount: Uint128::new(amount),
+        }
+    }
+
+    #[test]
+    fn test_channel_authorization() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            InstantiateMsg {
+                packet_lifetime: 60,
+            },
+        )
+        .unwrap();
+        let authorize = |deps: DepsMut, sender: &str, authorized: bool| {
+            let connection_id = "connection-2".to_string();
+            let counterparty_port_id = "their-port".to_string();
+            let msg = match authorized {
+                true => ExecuteMsg::AuthorizeChannel {
+                    connection_id,
+                    counterparty_port_id,
+                },
+                false => ExecuteMsg::RevokeChannel {
+                    connection_id,
+                    counterparty_port_id,
+                },
+            };
+            execute(deps, mock_env(), mock_info(sender, &[]), msg)
+        };
+        let open = |deps: DepsMut, channel: IbcChannel| {
+            ibc_channel_open(
+                deps,
+                mock_env(),
+                IbcChannelOpenMsg::new_try(channel, IBC_VERSION),
+            )
+        };
+        let connect = |deps: DepsMut, channel: IbcChannel| {
+            ibc_channel_connect(
+                deps,
+                mock_env(),
+                IbcChannelConnectMsg::new_ack(channel, IBC_VERSION),
+            )
+        };
+        let channels = |deps: Deps| {
+            let res: AuthorizedChannelsResponse =
+                from_binary(&query(deps, mock_env(), QueryMsg::AuthorizedChannels {}).unwrap())
+                    .unwrap();
+            res.channels
+        };
+
+        // Nothing may connect before the owner expects it.
+        let err = open(deps.as_mut(), channel()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Channel to their-port over connection-2 is not authorized"
+        );
+        let err = connect(deps.as_mut(), channel()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedChannel { .. }));
+        let err = authorize(deps.as_mut(), "mallory", true).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        authorize(deps.as_mut(), "owner", true).unwrap();
+        assert_eq!(
+            channels(deps.as_ref()),
+            vec![AuthorizedChannel {
+                connection_id: "connection-2".to_string(),
+                counterparty_port_id: "their-port".to_string(),
+            }]
+        );
+
+        // Other ports and connections stay locked out.
+        let mut other_port = channel();
+        other_port.counterparty_endpoint.port_id = "wasm.attacker".to_string();
+        let err = open(deps.as_mut(), other_port).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedChannel { .. }));
+        let mut other_connection = channel();
+        other_connection.connection_id = "connection-9".to_string();
+        let err = connect(deps.as_mut(), other_connection).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedChannel { .. }));
+        open(deps.as_mut(), channel()).unwrap();
+        connect(deps.as_mut(), channel()).unwrap();
+
+        // Packets must come from the bound counterparty.
+        let listing = PacketEnvelope {
+            version: IBC_VERSION.to_string(),
+            msg: PacketMsg::CreateLimitB {
+                id: 0,
+                limit: Limit {
+                    liquidty: token("ujuno", 100),
+                    liquidity_address: Addr::unchecked("maker"),
+                    liquidity_transfer_channel_id: "channel-1".to_string(),
+                    price_per_token: token("uosmo", 2),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                },
+            },
+        };
+        let mut packet = mock_ibc_packet_recv("channel-0", &listing).unwrap();
+        packet.packet.src.channel_id = "channel-666".to_string();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), packet).unwrap();
+        let ack: StdAck = from_binary(&res.acknowledgement).unwrap();
+        assert_eq!(
+            ack.unwrap_err(),
+            ContractError::UnboundChannel {}.to_string()
+        );
+        assert!(!LIMITS_B.has(&deps.storage, 0));
+        let packet = mock_ibc_packet_recv("channel-0", &listing).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), packet).unwrap();
+        assert_eq!(res.acknowledgement, StdAck::success(0));
+        assert!(LIMITS_B.has(&deps.storage, 0));
+
+        // Revoking does not unbind the channel.
+        authorize(deps.as_mut(), "owner", false).unwrap();
+        assert!(channels(deps.as_ref()).is_empty());
+        let config: State =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(config.endpoint.unwrap().channel_id, "channel-0");
+    }
+
+    #[test]
+    fn test_price_oracle() {
+        let mut deps = setup();
+        // The mock oracle prices ujuno at 2 uosmo and knows no other pair.
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "oracle" => {
+                match from_binary(msg).unwrap() {
+                    OracleQueryMsg::Price {
+                        base_denom,
+                        quote_denom,
+                    } if base_denom == "ujuno" && quote_denom == "uosmo" => {
+                        let price = PriceResponse {
+                            price: Decimal::percent(200),
+                        };
+                        SystemResult::Ok(ContractResult::Ok(to_binary(&price).unwrap()))
+                    }
+                    _ => SystemResult::Ok(ContractResult::Err("unknown pair".to_string())),
+                }
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                kind: "wasm".to_string(),
+            }),
+        });
+        let set_oracle = |deps: DepsMut, sender: &str, enforce: bool| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SetPriceOracle {
+                    oracle: Some(PriceOracleMsg {
+                        address: "oracle".to_string(),
+                        tolerance: Decimal::percent(50),
+                        enforce,
+                    }),
+                },
+            )
+        };
+        let err = set_oracle(deps.as_mut(), "maker", true).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        set_oracle(deps.as_mut(), "owner", true).unwrap();
+        let oracle: Option<PriceOracle> =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PriceOracle {}).unwrap())
+                .unwrap();
+        assert_eq!(oracle.unwrap().address, Addr::unchecked("oracle"));
+
+        let create = |deps: DepsMut, denom: &str, price: u128| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker", &coins(100, denom)),
+                ExecuteMsg::CreateLimit {
+                    price_per_token: token("uosmo", price),
+                    liquidity_transfer_channel_id: "channel-1".to_string(),
+                    ask_transfer_channel_id: "channel-2".to_string(),
+                },
+            )
+        };
+        // Prices of 1 to 3 uosmo per ujuno are within 50% of the oracle.
+        for price in [0, 4] {
+            let err = create(deps.as_mut(), "ujuno", price).unwrap_err();
+            assert!(matches!(err, ContractError::PriceDeviation { .. }));
+        }
+        let res = create(deps.as_mut(), "ujuno", 3).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .all(|attr| attr.key != "oracle_price"));
+        assert!(LIMITS_A.has(&deps.storage, 0));
+        // Pairs the oracle can not price are refused.
+        let err = create(deps.as_mut(), "uatom", 1).unwrap_err();
+        assert!(matches!(err, ContractError::NoOraclePrice { .. }));
+
+        // Without enforcement, deviating orders are created and flagged.
+        set_oracle(deps.as_mut(), "owner", false).unwrap();
+        let res = create(deps.as_mut(), "ujuno", 10).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "oracle_price" && attr.value == "2"));
+        assert!(LIMITS_A.has(&deps.storage, 1));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[]),
+            ExecuteMsg::SetPriceOracle { oracle: None },
+        )
+        .unwrap();
+        let res = create(deps.as_mut(), "ujuno", 10).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .all(|attr| attr.key != "oracle_price"));
+    }
+}
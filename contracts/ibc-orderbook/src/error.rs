@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, StdError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,6 +13,15 @@ pub enum ContractError {
     #[error("Already Connected")]
     AlreadyConnected {},
 
+    #[error("Channel to {port_id} over {connection_id} is not authorized")]
+    UnauthorizedChannel {
+        connection_id: String,
+        port_id: String,
+    },
+
+    #[error("Packet did not arrive over the bound channel")]
+    UnboundChannel {},
+
     #[error("Not Connected")]
     NotConnected {},
 
@@ -27,9 +36,33 @@ pub enum ContractError {
 
     #[error("Insufficient funds")]
     InsufficientFunds {},
+
+    #[error(
+        "Limit price deviates from the oracle price ({oracle_price}) by more than the tolerance"
+    )]
+    PriceDeviation { oracle_price: Decimal },
+
+    #[error("Price oracle has no price for {base_denom} in {quote_denom}")]
+    NoOraclePrice {
+        base_denom: String,
+        quote_denom: String,
+    },
+
+    #[error("Cannot migrate from a different contract ({previous_contract})")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from newer version ({previous_version}) to older ({new_version})")]
+    CannotMigrateVersion {
+        previous_version: String,
+        new_version: String,
+    },
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
 }
 
-/// Enum that can never be constructed. Used as an error type where we
-/// can not error.
-#[derive(Error, Debug)]
-pub enum Never {}
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
+}
@@ -0,0 +1,64 @@
+//! Wasm events emitted on every limit order transition. Each event
+//! carries the same attributes so indexers can follow an order across
+//! both chains by its `id`:
+//!
+//! | attribute      | value                                             |
+//! |----------------|---------------------------------------------------|
+//! | `id`           | limit id, shared by side A and side B             |
+//! | `side`         | `A` on the maker's chain, `B` on the taker's      |
+//! | `channel`      | IBC channel the order's packets travel over       |
+//! | `maker`        | address that provided the liquidity on side A     |
+//! | `taker`        | address filling the order, omitted if unknown     |
+//! | `liquidity_denom`, `liquidity_amount` | liquidity left in the order |
+//! | `price_denom`, `price_amount`         | price per token            |
+//! | `paid_amount`  | amount of `price_denom` paid by a fill            |
+//!
+//! Events, in lifecycle order:
+//!
+//! - `limit_created`: side A escrowed the liquidity and sent the order.
+//!   Carries the `oracle_price` if the order's price deviates from
+//!   the price oracle's beyond its tolerance.
+//! - `limit_listed`: side B stored the order.
+//! - `limit_accepted`: side B escrowed a taker's payment.
+//! - `limit_settled`: side A released liquidity to the taker, or side
+//!   B released the payment to the maker.
+
+use cosmwasm_std::{Event, Uint128};
+
+use crate::state::{denom_key, Limit};
+
+pub const LIMIT_CREATED: &str = "limit_created";
+pub const LIMIT_LISTED: &str = "limit_listed";
+pub const LIMIT_ACCEPTED: &str = "limit_accepted";
+pub const LIMIT_SETTLED: &str = "limit_settled";
+
+/// Builds a limit order transition event following the schema above.
+pub fn limit_event(
+    ty: &str,
+    id: u64,
+    side: &str,
+    channel: &str,
+    limit: &Limit,
+    fill: Option<(&str, Uint128)>,
+) -> Event {
+    let event = Event::new(ty)
+        .add_attribute("id", id.to_string())
+        .add_attribute("side", side)
+        .add_attribute("channel", channel)
+        .add_attribute("maker", limit.liquidity_address.as_str());
+    // Empty attribute values are rejected by the SDK, so the taker
+    // and fill are left out of events that have none.
+    let event = match fill {
+        Some((taker, _)) => event.add_attribute("taker", taker),
+        None => event,
+    };
+    let event = event
+        .add_attribute("liquidity_denom", denom_key(&limit.liquidty.denom))
+        .add_attribute("liquidity_amount", limit.liquidty.amount)
+        .add_attribute("price_denom", denom_key(&limit.price_per_token.denom))
+        .add_attribute("price_amount", limit.price_per_token.amount);
+    match fill {
+        Some((_, paid)) => event.add_attribute("paid_amount", paid),
+        None => event,
+    }
+}
@@ -1,28 +1,36 @@
 use cosmwasm_std::{
-    entry_point, from_slice, Coin, DepsMut, Env, IbcBasicResponse, IbcChannelCloseMsg,
-    IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse, IbcMsg, IbcPacketAckMsg,
-    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult, Uint128,
+    entry_point, Coin, Deps, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel,
+    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse, IbcMsg,
+    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, StdResult,
+    Uint128,
 };
 use cw20::Denom;
 
-//use crate::error::Never;
-
-use crate::ibc_helpers::{validate_order_and_version, StdAck};
+use crate::ibc_helpers::{decode_packet, negotiate_version, validate_order_and_version, StdAck};
 
 use crate::error::ContractError;
+use crate::events::{limit_event, LIMIT_LISTED, LIMIT_SETTLED};
 use crate::msg::PacketMsg;
-use crate::state::{Limit, LIMITS_A, LIMITS_B, STATE};
+use crate::state::{Limit, AUTHORIZED_CHANNELS, LIMITS_A, LIMITS_B, STATE};
 
-pub const IBC_VERSION: &str = "orderbook-1";
+pub const IBC_VERSION_PREFIX: &str = "orderbook-";
+/// The highest protocol revision we speak, proposed when opening channels.
+pub const IBC_VERSION: &str = "orderbook-2";
+/// Revision that exchanges bare `PacketMsg`s rather than envelopes.
+pub const LEGACY_IBC_REVISION: u32 = 1;
+pub const MIN_IBC_REVISION: u32 = 1;
+pub const MAX_IBC_REVISION: u32 = 2;
 
 #[entry_point]
-/// enforces ordering and versioing constraints
+/// enforces ordering and negotiates the channel version
 pub fn ibc_channel_open(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     msg: IbcChannelOpenMsg,
 ) -> Result<IbcChannelOpenResponse, ContractError> {
-    validate_order_and_version(msg.channel(), msg.counterparty_version())
+    check_authorized(deps.as_ref(), msg.channel())?;
+    let version = negotiate_version(msg.channel(), msg.counterparty_version())?;
+    Ok(Some(Ibc3ChannelOpenResponse { version }))
 }
 
 #[entry_point]
@@ -32,7 +40,8 @@ pub fn ibc_channel_connect(
     _env: Env,
     msg: IbcChannelConnectMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    validate_order_and_version(msg.channel(), msg.counterparty_version())?;
+    check_authorized(deps.as_ref(), msg.channel())?;
+    let version = validate_order_and_version(msg.channel(), msg.counterparty_version())?;
 
     let mut state = STATE.load(deps.storage)?;
     if state.endpoint.is_some() {
@@ -45,13 +54,29 @@ pub fn ibc_channel_connect(
     }
 
     state.counterparty_endpoint = Some(msg.channel().counterparty_endpoint.clone());
+    state.ibc_version = Some(version.clone());
 
     STATE.save(deps.storage, &state)?;
 
     Ok(IbcBasicResponse::new()
         .add_attribute("method", "ibc_channel_connect")
         .add_attribute("channel", &msg.channel().endpoint.channel_id)
-        .add_attribute("port", &msg.channel().endpoint.port_id))
+        .add_attribute("port", &msg.channel().endpoint.port_id)
+        .add_attribute("version", version))
+}
+
+/// Only channels the owner expects may be opened, so no other contract
+/// can claim the single channel this one binds.
+fn check_authorized(deps: Deps, channel: &IbcChannel) -> Result<(), ContractError> {
+    let connection_id = &channel.connection_id;
+    let port_id = &channel.counterparty_endpoint.port_id;
+    if !AUTHORIZED_CHANNELS.has(deps.storage, (connection_id, port_id)) {
+        return Err(ContractError::UnauthorizedChannel {
+            connection_id: connection_id.clone(),
+            port_id: port_id.clone(),
+        });
+    }
+    Ok(())
 }
 
 #[entry_point]
@@ -83,15 +108,30 @@ pub fn ibc_packet_receive(
     env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let packet_msg: StdResult<PacketMsg> = from_slice(&msg.packet.data);
-    /*if packet_msg.is_err() {
+    let state = STATE.load(deps.storage)?;
+    let bound = match (&state.endpoint, &state.counterparty_endpoint) {
+        (Some(endpoint), Some(counterparty)) => {
+            msg.packet.dest.channel_id == endpoint.channel_id && msg.packet.src == *counterparty
+        }
+        _ => false,
+    };
+    if !bound {
         return Ok(IbcReceiveResponse::new()
             .add_attribute("method", "ibc_packet_receive")
-            .add_attribute("error", "invalid packet data")
-            .set_ack(StdAck::fail("invalid packet data".to_string())));
-    }*/
+            .set_ack(StdAck::fail(ContractError::UnboundChannel {}.to_string())));
+    }
+
+    let packet_msg = match decode_packet(state.ibc_version.as_deref(), &msg.packet.data) {
+        Ok(packet_msg) => packet_msg,
+        Err(err) => {
+            return Ok(IbcReceiveResponse::new()
+                .add_attribute("method", "ibc_packet_receive")
+                .add_attribute("error", "invalid packet data")
+                .set_ack(StdAck::fail(err.to_string())))
+        }
+    };
 
-    match packet_msg.unwrap() {
+    match packet_msg {
         PacketMsg::CreateLimitB { id, limit } => create_limit_b(deps, env, id, limit, msg),
         PacketMsg::AcceptLimitA { id, amount, sender } => {
             accept_limit_a(deps, env, id, amount, sender, msg)
@@ -104,12 +144,20 @@ pub fn create_limit_b(
     _env: Env,
     id: u64,
     limit: Limit,
-    _msg: IbcPacketReceiveMsg,
+    msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     LIMITS_B.save(deps.storage, id, &limit).unwrap();
     Ok(IbcReceiveResponse::new()
         .add_attribute("method", "ibc_packet_receive")
-        .set_ack(StdAck::success(&id)))
+        .add_event(limit_event(
+            LIMIT_LISTED,
+            id,
+            "B",
+            &msg.packet.dest.channel_id,
+            &limit,
+            None,
+        ))
+        .set_ack(StdAck::success(id)))
 }
 
 pub fn accept_limit_a(
@@ -118,7 +166,7 @@ pub fn accept_limit_a(
     id: u64,
     amount: Uint128,
     sender: String,
-    _msg: IbcPacketReceiveMsg,
+    msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     let state = STATE.load(deps.storage)?;
     let mut limit = LIMITS_A.load(deps.storage, id)?;
@@ -128,6 +176,14 @@ pub fn accept_limit_a(
                 denom,
                 amount: amount.checked_div(limit.price_per_token.amount).unwrap(),
             };
+            let event = limit_event(
+                LIMIT_SETTLED,
+                id,
+                "A",
+                &msg.packet.dest.channel_id,
+                &limit,
+                Some((&sender, amount)),
+            );
             let transfer_msg = IbcMsg::Transfer {
                 channel_id: limit.liquidity_transfer_channel_id.clone(),
                 to_address: sender,
@@ -142,13 +198,14 @@ pub fn accept_limit_a(
                 .unwrap();
             LIMITS_A.save(deps.storage, id, &limit).unwrap();
 
-            return Ok(IbcReceiveResponse::new()
+            Ok(IbcReceiveResponse::new()
                 .add_attribute("method", "ibc_packet_receive")
                 .add_message(transfer_msg)
-                .set_ack(StdAck::success(&id)));
+                .add_event(event)
+                .set_ack(StdAck::success(id)))
         }
         Denom::Cw20(_) => unimplemented!(),
-    };
+    }
 }
 
 #[entry_point]
@@ -158,7 +215,8 @@ pub fn ibc_packet_ack(
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     // we need to parse the ack based on our request
-    let original_packet: PacketMsg = from_slice(&msg.original_packet.data)?;
+    let version = STATE.load(deps.storage)?.ibc_version;
+    let original_packet = decode_packet(version.as_deref(), &msg.original_packet.data)?;
 
     match original_packet {
         PacketMsg::AcceptLimitA { id, amount, sender } => {
@@ -166,10 +224,7 @@ pub fn ibc_packet_ack(
             let state = STATE.load(deps.storage)?;
             match limit.price_per_token.denom.clone() {
                 Denom::Native(denom) => {
-                    let coin = Coin {
-                        denom,
-                        amount: amount,
-                    };
+                    let coin = Coin { denom, amount };
                     let transfer_msg = IbcMsg::Transfer {
                         channel_id: limit.ask_transfer_channel_id.clone(),
                         to_address: limit.liquidity_address.to_string(),
@@ -184,15 +239,24 @@ pub fn ibc_packet_ack(
                         .unwrap();
 
                     LIMITS_B.save(deps.storage, id, &limit).unwrap();
-                    return Ok(IbcBasicResponse::new()
+                    let event = limit_event(
+                        LIMIT_SETTLED,
+                        id,
+                        "B",
+                        &msg.original_packet.src.channel_id,
+                        &limit,
+                        Some((&sender, amount)),
+                    );
+                    Ok(IbcBasicResponse::new()
                         .add_attribute("method", "ibc_packet_ack")
-                        .add_message(transfer_msg));
+                        .add_message(transfer_msg)
+                        .add_event(event))
                 }
                 Denom::Cw20(_) => unimplemented!(),
-            };
+            }
         }
         PacketMsg::CreateLimitB { id: _, limit: _ } => {
-            return Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_ack"))
+            Ok(IbcBasicResponse::new().add_attribute("method", "ibc_packet_ack"))
         }
     }
 }
@@ -205,5 +269,5 @@ pub fn ibc_packet_timeout(
 ) -> StdResult<IbcBasicResponse> {
     //TODO: return funds and roll back state.
 
-    Ok(IbcBasicResponse::new().add_attribute("action", "ibc_packet_timeout"))
+    Ok(IbcBasicResponse::new().add_attribute("method", "ibc_packet_timeout"))
 }
@@ -1,10 +1,14 @@
 use cosmwasm_std::{
     from_binary, from_slice, to_binary, Binary, IbcAcknowledgement, IbcChannel, IbcEndpoint,
-    IbcOrder,
+    IbcOrder, StdResult,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{ibc::IBC_VERSION, ContractError};
+use crate::ibc::{
+    IBC_VERSION, IBC_VERSION_PREFIX, LEGACY_IBC_REVISION, MAX_IBC_REVISION, MIN_IBC_REVISION,
+};
+use crate::msg::{PacketEnvelope, PacketMsg};
+use crate::ContractError;
 
 /// Tries to remove the source prefix from a given class_id. If the
 /// class_id does not begin with the given prefix, returns
@@ -98,48 +102,126 @@ pub fn try_get_ack_error(ack: &IbcAcknowledgement) -> Option<String> {
     }
 }
 
-/// Validates order and version information for ics721. We expect
-/// ics721-1 as the version and an unordered channel.
-pub(crate) fn validate_order_and_version(
-    channel: &IbcChannel,
-    counterparty_version: Option<&str>,
-) -> Result<(), ContractError> {
+/// Parses the protocol revision out of a channel version string. For
+/// example, `orderbook-2` parses to `Some(2)`. Returns `None` for
+/// anything that is not one of our versions.
+pub(crate) fn parse_version(version: &str) -> Option<u32> {
+    version.strip_prefix(IBC_VERSION_PREFIX)?.parse().ok()
+}
+
+/// Errors unless `version` names a protocol revision this contract
+/// can speak.
+pub(crate) fn validate_version(version: &str) -> Result<u32, ContractError> {
+    match parse_version(version) {
+        Some(revision) if (MIN_IBC_REVISION..=MAX_IBC_REVISION).contains(&revision) => Ok(revision),
+        _ => Err(ContractError::InvalidVersion {
+            actual: version.to_string(),
+            expected: format!(
+                "{}{}..={}{}",
+                IBC_VERSION_PREFIX, MIN_IBC_REVISION, IBC_VERSION_PREFIX, MAX_IBC_REVISION
+            ),
+        }),
+    }
+}
+
+/// Validates the channel ordering. We expect an unordered channel.
+pub(crate) fn validate_order(channel: &IbcChannel) -> Result<(), ContractError> {
     // We expect an unordered channel here. Ordered channels have the
     // property that if a message is lost the entire channel will stop
     // working until you start it again.
     if channel.order != IbcOrder::Unordered {
         return Err(ContractError::OrderedChannel {});
     }
+    Ok(())
+}
 
-    if channel.version != IBC_VERSION {
-        return Err(ContractError::InvalidVersion {
-            actual: channel.version.to_string(),
-            expected: IBC_VERSION.to_string(),
-        });
-    }
-
-    // Make sure that we're talking with a counterparty who speaks the
-    // same "protocol" as us.
-    //
-    // For a connection between chain A and chain B being established
-    // by chain A, chain B knows counterparty information during
-    // `OpenTry` and chain A knows counterparty information during
-    // `OpenAck`. We verify it when we have it but when we don't it's
-    // alright.
-    if let Some(counterparty_version) = counterparty_version {
-        if counterparty_version != IBC_VERSION {
-            return Err(ContractError::InvalidVersion {
-                actual: counterparty_version.to_string(),
-                expected: IBC_VERSION.to_string(),
-            });
+/// Picks the version to answer a channel handshake with.
+///
+/// During `OpenInit` we propose our highest revision, unless the
+/// relayer pinned a specific one that we support. During `OpenTry`
+/// the counterparty has proposed its highest revision, so we answer
+/// with the highest revision that both sides speak. The initiating
+/// side accepts that answer in `OpenAck`.
+pub(crate) fn negotiate_version(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<String, ContractError> {
+    validate_order(channel)?;
+
+    match counterparty_version {
+        Some(counterparty_version) => {
+            let proposed = parse_version(counterparty_version).ok_or_else(|| {
+                ContractError::InvalidVersion {
+                    actual: counterparty_version.to_string(),
+                    expected: IBC_VERSION.to_string(),
+                }
+            })?;
+            let revision = proposed.min(MAX_IBC_REVISION);
+            let version = format!("{}{}", IBC_VERSION_PREFIX, revision);
+            validate_version(&version)?;
+            Ok(version)
+        }
+        None if channel.version.is_empty() => Ok(IBC_VERSION.to_string()),
+        None => {
+            validate_version(&channel.version)?;
+            Ok(channel.version.clone())
         }
     }
+}
 
-    Ok(())
+/// Validates order and version information once the handshake
+/// completes and returns the version the channel settled on.
+///
+/// During `OpenAck` the counterparty version is the one chosen by the
+/// other side in `OpenTry`, which may be lower than what we proposed,
+/// so it takes precedence over our own channel version.
+pub(crate) fn validate_order_and_version(
+    channel: &IbcChannel,
+    counterparty_version: Option<&str>,
+) -> Result<String, ContractError> {
+    validate_order(channel)?;
+
+    let version = counterparty_version.unwrap_or(&channel.version);
+    validate_version(version)?;
+
+    Ok(version.to_string())
+}
+
+/// Serializes a packet in the format understood by the channel's
+/// negotiated version. Legacy `orderbook-1` channels receive a bare
+/// `PacketMsg`, everything newer receives a `PacketEnvelope`.
+pub(crate) fn encode_packet(version: Option<&str>, msg: &PacketMsg) -> StdResult<Binary> {
+    match version.and_then(parse_version) {
+        Some(revision) if revision > LEGACY_IBC_REVISION => to_binary(&PacketEnvelope {
+            version: IBC_VERSION.to_string(),
+            msg: msg.clone(),
+        }),
+        _ => to_binary(msg),
+    }
+}
+
+/// Deserializes a packet sent in either the enveloped or the legacy
+/// bare format, so that both sides keep working while one of them is
+/// being upgraded. Envelopes must carry the channel's negotiated
+/// `version`.
+pub(crate) fn decode_packet(
+    version: Option<&str>,
+    data: &Binary,
+) -> Result<PacketMsg, ContractError> {
+    match from_slice::<PacketEnvelope>(data) {
+        Ok(envelope) if version == Some(envelope.version.as_str()) => Ok(envelope.msg),
+        Ok(envelope) => Err(ContractError::InvalidVersion {
+            actual: envelope.version,
+            expected: version.unwrap_or_default().to_string(),
+        }),
+        Err(_) => Ok(from_slice::<PacketMsg>(data)?),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use cosmwasm_std::testing::mock_ibc_channel;
+
     use super::*;
 
     #[test]
@@ -194,4 +276,41 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_negotiate_version() {
+        let channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, "");
+
+        // OpenInit with no pinned version proposes our highest.
+        assert_eq!(negotiate_version(&channel, None).unwrap(), IBC_VERSION);
+
+        // OpenTry answers with the highest revision both sides speak.
+        assert_eq!(
+            negotiate_version(&channel, Some("orderbook-1")).unwrap(),
+            "orderbook-1"
+        );
+        assert_eq!(
+            negotiate_version(&channel, Some("orderbook-9")).unwrap(),
+            IBC_VERSION
+        );
+
+        negotiate_version(&channel, Some("orderbook-0")).unwrap_err();
+        negotiate_version(&channel, Some("ics721-1")).unwrap_err();
+
+        let ordered = mock_ibc_channel("channel-0", IbcOrder::Ordered, IBC_VERSION);
+        negotiate_version(&ordered, None).unwrap_err();
+    }
+
+    #[test]
+    fn test_validate_order_and_version_prefers_counterparty() {
+        let channel = mock_ibc_channel("channel-0", IbcOrder::Unordered, IBC_VERSION);
+        assert_eq!(
+            validate_order_and_version(&channel, Some("orderbook-1")).unwrap(),
+            "orderbook-1"
+        );
+        assert_eq!(
+            validate_order_and_version(&channel, None).unwrap(),
+            IBC_VERSION
+        );
+    }
 }
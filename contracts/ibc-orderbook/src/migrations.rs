@@ -0,0 +1,122 @@
+use cosmwasm_std::{StdResult, Storage};
+use semver::Version;
+
+use crate::ibc::{IBC_VERSION_PREFIX, LEGACY_IBC_REVISION};
+use crate::state::{State, STATE};
+
+/// First release with a `migrate` entry point. Channels opened before
+/// it always spoke the legacy protocol revision.
+pub const V0_2_0: Version = Version::new(0, 2, 0);
+
+/// Runs every storage migration between the stored contract version
+/// and the current one, oldest first.
+pub fn migrate_storage(storage: &mut dyn Storage, from: &Version) -> StdResult<()> {
+    if *from < V0_2_0 {
+        v0_1::migrate_state(storage)?;
+    }
+    Ok(())
+}
+
+/// Storage layout written by 0.1.x releases. Limits are stored as
+/// they were, only `State` gained a field.
+mod v0_1 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, IbcEndpoint};
+    use cw_storage_plus::Item;
+
+    use super::*;
+
+    #[cw_serde]
+    pub struct OldState {
+        pub owner: Addr,
+        pub packet_lifetime: u64,
+        pub endpoint: Option<IbcEndpoint>,
+        pub counterparty_endpoint: Option<IbcEndpoint>,
+    }
+
+    const OLD_STATE: Item<OldState> = Item::new("state");
+
+    /// Records the legacy protocol revision for an already bound
+    /// channel, since 0.1.x did not negotiate one.
+    pub fn migrate_state(storage: &mut dyn Storage) -> StdResult<()> {
+        let old = OLD_STATE.load(storage)?;
+        let ibc_version = old
+            .endpoint
+            .as_ref()
+            .map(|_| format!("{}{}", IBC_VERSION_PREFIX, LEGACY_IBC_REVISION));
+        STATE.save(
+            storage,
+            &State {
+                owner: old.owner,
+                packet_lifetime: old.packet_lifetime,
+                endpoint: old.endpoint,
+                counterparty_endpoint: old.counterparty_endpoint,
+                ibc_version,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{to_vec, Addr, IbcEndpoint, Uint128};
+    use cw20::Denom;
+
+    use super::v0_1::OldState;
+    use super::*;
+    use crate::contract::migrate;
+    use crate::msg::MigrateMsg;
+    use crate::state::{Limit, Token, LIMITS_A};
+    use crate::ContractError;
+
+    #[test]
+    fn test_migrate_from_legacy_name() {
+        let mut deps = mock_dependencies();
+        cw2::set_contract_version(&mut deps.storage, "crates.io:ibc-native-swap", "0.1.0").unwrap();
+        let endpoint = IbcEndpoint {
+            port_id: "wasm.contract".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let old_state = OldState {
+            owner: Addr::unchecked("owner"),
+            packet_lifetime: 60,
+            endpoint: Some(endpoint.clone()),
+            counterparty_endpoint: Some(endpoint),
+        };
+        deps.storage.set(b"state", &to_vec(&old_state).unwrap());
+        let limit = Limit {
+            liquidty: Token {
+                denom: Denom::Native("ujuno".to_string()),
+                amount: Uint128::new(100),
+            },
+            liquidity_address: Addr::unchecked("maker"),
+            liquidity_transfer_channel_id: "channel-1".to_string(),
+            price_per_token: Token {
+                denom: Denom::Native("uosmo".to_string()),
+                amount: Uint128::new(2),
+            },
+            ask_transfer_channel_id: "channel-2".to_string(),
+        };
+        LIMITS_A.save(&mut deps.storage, 0, &limit).unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.ibc_version.as_deref(), Some("orderbook-1"));
+        assert_eq!(LIMITS_A.load(&deps.storage, 0).unwrap(), limit);
+        let version = cw2::get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.contract, "crates.io:ibc-orderbook");
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+        // A second run is a no-op, but the swap contract's own releases
+        // and downgrades are refused.
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        cw2::set_contract_version(&mut deps.storage, "crates.io:ibc-native-swap", "0.2.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrate { .. }));
+        cw2::set_contract_version(&mut deps.storage, "crates.io:ibc-orderbook", "9.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotMigrateVersion { .. }));
+    }
+}
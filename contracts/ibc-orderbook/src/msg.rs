@@ -1,12 +1,15 @@
-use crate::state::{Limit, Token};
+use crate::state::{Limit, PriceOracle, State, Token};
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Decimal, Uint128};
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub packet_lifetime: u64,
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     CreateLimit {
@@ -24,6 +27,51 @@ pub enum ExecuteMsg {
     AcceptLimit {
         id: u64,
     },
+    /// Owner: sets the oracle new limit orders are priced against, or
+    /// removes it with `None`.
+    SetPriceOracle {
+        oracle: Option<PriceOracleMsg>,
+    },
+    /// Owner: lets a channel to `counterparty_port_id` over
+    /// `connection_id` be opened and bound.
+    AuthorizeChannel {
+        connection_id: String,
+        counterparty_port_id: String,
+    },
+    /// Owner: withdraws a channel authorization. A channel already
+    /// bound stays bound.
+    RevokeChannel {
+        connection_id: String,
+        counterparty_port_id: String,
+    },
+}
+
+#[cw_serde]
+pub struct PriceOracleMsg {
+    pub address: String,
+    /// Largest relative deviation from the oracle's price allowed,
+    /// `0.5` lets an order ask up to 50% more or less than the oracle.
+    pub tolerance: Decimal,
+    /// Rejects deviating orders when set, otherwise they are only
+    /// flagged in their `limit_created` event.
+    pub enforce: bool,
+}
+
+/// Query answered by the price oracle.
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    /// Price of one unit of `base_denom` in units of `quote_denom`.
+    #[returns(PriceResponse)]
+    Price {
+        base_denom: String,
+        quote_denom: String,
+    },
+}
+
+#[cw_serde]
+pub struct PriceResponse {
+    pub price: Decimal,
 }
 
 #[cw_serde]
@@ -32,6 +80,43 @@ pub enum QueryMsg {
     // GetCount returns the current count as a json-encoded number
     #[returns(Limit)]
     GetLimitOrder { side: String, id: u64 },
+    /// Owner, packet lifetime and the channel this contract is bound to.
+    #[returns(State)]
+    Config {},
+    /// Oracle new limit orders are priced against, if any.
+    #[returns(Option<PriceOracle>)]
+    PriceOracle {},
+    /// Contract version, IBC protocol versions and supported features.
+    #[returns(ContractInfoResponse)]
+    ContractInfo {},
+    /// Channel handshakes the owner authorized.
+    #[returns(AuthorizedChannelsResponse)]
+    AuthorizedChannels {},
+}
+
+#[cw_serde]
+pub struct AuthorizedChannel {
+    pub connection_id: String,
+    pub counterparty_port_id: String,
+}
+
+#[cw_serde]
+pub struct AuthorizedChannelsResponse {
+    pub channels: Vec<AuthorizedChannel>,
+}
+
+#[cw_serde]
+pub struct ContractInfoResponse {
+    /// cw2 contract name.
+    pub contract: String,
+    /// cw2 contract version.
+    pub version: String,
+    /// Highest IBC protocol version, proposed when opening channels.
+    pub ibc_version: String,
+    pub supported_ibc_versions: Vec<String>,
+    /// Version negotiated on the bound channel, if any.
+    pub channel_version: Option<String>,
+    pub features: Vec<String>,
 }
 
 #[cw_serde]
@@ -46,3 +131,12 @@ pub enum PacketMsg {
         sender: String,
     },
 }
+
+/// Wire format of packets on channels that negotiated `orderbook-2`
+/// or later. Channels still on `orderbook-1` exchange a bare
+/// `PacketMsg`.
+#[cw_serde]
+pub struct PacketEnvelope {
+    pub version: String,
+    pub msg: PacketMsg,
+}
@@ -1,7 +1,7 @@
 use cosmwasm_schema::cw_serde;
 use cw20::Denom;
 
-use cosmwasm_std::{Addr, Coin, IbcEndpoint, Uint128};
+use cosmwasm_std::{Addr, Decimal, Empty, IbcEndpoint, Uint128};
 use cw_storage_plus::{Item, Map};
 
 #[cw_serde]
@@ -16,6 +16,8 @@ pub struct State {
     pub packet_lifetime: u64,
     pub endpoint: Option<IbcEndpoint>,
     pub counterparty_endpoint: Option<IbcEndpoint>,
+    /// Channel version negotiated during the handshake.
+    pub ibc_version: Option<String>,
 }
 
 #[cw_serde]
@@ -27,10 +29,35 @@ pub struct Limit {
     pub ask_transfer_channel_id: String,
 }
 
+/// Oracle the price of new limit orders is checked against.
+#[cw_serde]
+pub struct PriceOracle {
+    pub address: Addr,
+    /// Largest relative deviation from the oracle's price allowed.
+    pub tolerance: Decimal,
+    /// Whether deviating orders are rejected, or only flagged in their
+    /// `limit_created` event.
+    pub enforce: bool,
+}
+
 pub const STATE: Item<State> = Item::new("state");
 
+pub const PRICE_ORACLE: Item<PriceOracle> = Item::new("price_oracle");
+
+/// Channel handshakes the owner expects, keyed by connection id and
+/// counterparty port id. No other channel may be opened.
+pub const AUTHORIZED_CHANNELS: Map<(&str, &str), Empty> = Map::new("authorized_channels");
+
 pub const LIMIT_ID: Item<u64> = Item::new("limit_id");
 
 pub const LIMITS_A: Map<u64, Limit> = Map::new("limits_a");
 
 pub const LIMITS_B: Map<u64, Limit> = Map::new("limits_b");
+
+/// Key used to index a denom, the native denom or the cw20 address.
+pub fn denom_key(denom: &Denom) -> String {
+    match denom {
+        Denom::Native(denom) => denom.clone(),
+        Denom::Cw20(addr) => addr.to_string(),
+    }
+}
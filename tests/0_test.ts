@@ -113,6 +113,29 @@ async function demoSetup(): Promise<SetupInfo> {
     // create a connection and channel for ibc-swap
     const [src, dest] = await setup(wasmd, osmosis);
     const link = await Link.createWithNewConnections(src, dest);
+    // each contract only accepts the channel its owner expects
+    await wasmClient.sign.execute(
+        wasmClient.senderAddress,
+        wasmSwap,
+        {
+            authorize_channel: {
+                connection_id: link.endA.connectionID,
+                counterparty_port_id: osmoSwapPort,
+            },
+        },
+        "auto"
+    );
+    await osmoClient.sign.execute(
+        osmoClient.senderAddress,
+        osmoSwap,
+        {
+            authorize_channel: {
+                connection_id: link.endB.connectionID,
+                counterparty_port_id: wasmSwapPort,
+            },
+        },
+        "auto"
+    );
     const channelInfo = await link.createChannel(
         "A",
         wasmSwapPort,
@@ -155,6 +178,28 @@ async function demoSetup(): Promise<SetupInfo> {
     );
     assert(osmoLimitPort);
 
+    await wasmClient.sign.execute(
+        wasmClient.senderAddress,
+        wasmLimit,
+        {
+            authorize_channel: {
+                connection_id: link.endA.connectionID,
+                counterparty_port_id: osmoLimitPort,
+            },
+        },
+        "auto"
+    );
+    await osmoClient.sign.execute(
+        osmoClient.senderAddress,
+        osmoLimit,
+        {
+            authorize_channel: {
+                connection_id: link.endB.connectionID,
+                counterparty_port_id: wasmLimitPort,
+            },
+        },
+        "auto"
+    );
     const limitChannelInfo = await link.createChannel(
         "A",
         wasmLimitPort,
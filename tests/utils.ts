@@ -22,8 +22,8 @@ const {
 
 const osmosis = { ...oldOsmo, minFee: "0.025uosmo" };
 
-export const SwapIbcVersion = "native-swap-1";
-export const OrderbookIbcVersion = "orderbook-1";
+export const SwapIbcVersion = "native-swap-2";
+export const OrderbookIbcVersion = "orderbook-2";
 
 export async function setupContracts(
     cosmwasm: CosmWasmSigner,